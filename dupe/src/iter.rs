@@ -7,7 +7,7 @@
  * of this source tree.
  */
 
-use std::iter::Cloned;
+use core::iter::Cloned;
 
 use crate::Dupe;
 
@@ -8,14 +8,21 @@
  */
 
 //! A cheap version of [`Clone`].
+//!
+//! The crate is `no_std` compatible (it only requires `alloc`) when the default
+//! `std` feature is disabled.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 pub(crate) mod iter;
 pub(crate) mod option;
 
-use std::cell::Cell;
-use std::num::*;
-use std::rc::Rc;
-use std::sync::Arc;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::cell::Cell;
+use core::num::*;
 
 pub use dupe_derive::Clone_;
 pub use dupe_derive::Copy_;
@@ -40,18 +47,18 @@ impl<A: ?Sized> Dupe for &A {}
 impl<A: ?Sized> Dupe for *const A {}
 impl<A: ?Sized> Dupe for *mut A {}
 impl<A: ?Sized> Dupe for Arc<A> {}
-impl<A: ?Sized> Dupe for std::sync::Weak<A> {}
+impl<A: ?Sized> Dupe for alloc::sync::Weak<A> {}
 impl<A: ?Sized> Dupe for Rc<A> {}
-impl<A: ?Sized> Dupe for std::rc::Weak<A> {}
+impl<A: ?Sized> Dupe for alloc::rc::Weak<A> {}
 impl<A: Copy> Dupe for Cell<A> {}
 
 // Small containers
 impl<A: Dupe> Dupe for Option<A> {}
 impl<T: Dupe, E: Dupe> Dupe for Result<T, E> {}
-impl<A: Dupe> Dupe for std::ops::Bound<A> {}
-impl<A: Dupe> Dupe for std::pin::Pin<A> {}
-impl<A: Dupe> Dupe for std::ptr::NonNull<A> {}
-impl<A: Dupe> Dupe for std::task::Poll<A> {}
+impl<A: Dupe> Dupe for core::ops::Bound<A> {}
+impl<A: Dupe> Dupe for core::pin::Pin<A> {}
+impl<A: Dupe> Dupe for core::ptr::NonNull<A> {}
+impl<A: Dupe> Dupe for core::task::Poll<A> {}
 impl<A: Dupe> Dupe for (A,) {}
 // Not clear if Dupe should be implemented for pairs or not.
 // Concern is deeply nested pairs could be exponentially more expensive than their inner dupes.
@@ -88,17 +95,20 @@ impl Dupe for NonZeroI128 {}
 impl Dupe for NonZeroIsize {}
 
 // Other std types that are Copyable
-impl Dupe for std::any::TypeId {}
-impl Dupe for std::marker::PhantomPinned {}
-impl Dupe for std::net::Ipv4Addr {}
-impl Dupe for std::net::Ipv6Addr {}
-impl Dupe for std::net::SocketAddrV4 {}
-impl Dupe for std::net::SocketAddrV6 {}
+impl Dupe for core::any::TypeId {}
+impl Dupe for core::marker::PhantomPinned {}
+impl Dupe for core::net::Ipv4Addr {}
+impl Dupe for core::net::Ipv6Addr {}
+impl Dupe for core::net::SocketAddrV4 {}
+impl Dupe for core::net::SocketAddrV6 {}
+#[cfg(feature = "std")]
 impl Dupe for std::thread::ThreadId {}
+#[cfg(feature = "std")]
 impl Dupe for std::time::Instant {}
+#[cfg(feature = "std")]
 impl Dupe for std::time::SystemTime {}
-impl Dupe for std::time::Duration {}
-impl<T: ?Sized> Dupe for std::marker::PhantomData<T> {}
+impl Dupe for core::time::Duration {}
+impl<T: ?Sized> Dupe for core::marker::PhantomData<T> {}
 
 impl<R> Dupe for fn() -> R {}
 impl<A1, R> Dupe for fn(A1) -> R {}
@@ -0,0 +1,100 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Measure the cost of dereferencing an `ARef`, and of creating and dropping one.
+//!
+//! We deref a collection of `ARef`s which are either all pointers, all borrows, or a random
+//! mixture of the two, and compare each against a collection of plain `(&T, usize)` pairs,
+//! which have the same size and point at the same values. If `deref` had to branch on the
+//! representation the mixed case would pay for branch mispredictions, so all three ratios
+//! should be close to 1.
+//!
+//! We then create, deref and drop an `ARef` per item. A pointer should cost the same as
+//! plain `&T`, while a borrow pays for bumping and releasing the `RefCell` borrow count.
+//!
+//! Run with `cargo bench -p gazebo --bench aref`.
+
+use std::cell::RefCell;
+use std::hint::black_box;
+use std::time::Instant;
+
+use gazebo::cell::ARef;
+
+const ITEMS: usize = 10_000;
+const ROUNDS: u32 = 1_000;
+
+/// Nanoseconds per call of `get`, over every item.
+fn time<T>(xs: &[T], get: impl Fn(&T) -> u64) -> f64 {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut total = 0u64;
+        for x in black_box(xs) {
+            total = total.wrapping_add(get(x));
+        }
+        black_box(total);
+    }
+    start.elapsed().as_nanos() as f64 / (ROUNDS as usize * xs.len()) as f64
+}
+
+fn measure_deref(name: &str, arefs: &[ARef<u64>]) {
+    let plain: Vec<(&u64, usize)> = arefs.iter().map(|x| (&**x, 0)).collect();
+    let baseline = time(&plain, |x| *x.0);
+    let res = time(arefs, |x| **x);
+    println!(
+        "{:<8} {:>6.3} ns/deref, {:>6.3} ns for &T, {:.2}x",
+        name,
+        res,
+        baseline,
+        res / baseline
+    );
+}
+
+fn measure_create<T>(name: &str, xs: &[T], get: impl Fn(&T) -> u64) {
+    println!(
+        "{:<8} {:>6.3} ns/create+deref+drop",
+        name,
+        time(xs, |x| black_box(get(x)))
+    );
+}
+
+fn main() {
+    let values: Vec<u64> = (0..ITEMS as u64).collect();
+    let cells: Vec<RefCell<u64>> = values.iter().map(|x| RefCell::new(*x)).collect();
+
+    // A cheap xorshift so the mixture has no pattern for the branch predictor to learn.
+    let mut seed = 0x2545F4914F6CDD1Du64;
+    let mut coin = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed & 1 == 0
+    };
+
+    let plain: Vec<ARef<u64>> = values.iter().map(ARef::new_ptr).collect();
+    let borrowed: Vec<ARef<u64>> = cells.iter().map(|x| ARef::new_ref(x.borrow())).collect();
+    let mixed: Vec<ARef<u64>> = values
+        .iter()
+        .zip(&cells)
+        .map(|(v, c)| {
+            if coin() {
+                ARef::new_ptr(v)
+            } else {
+                ARef::new_ref(c.borrow())
+            }
+        })
+        .collect();
+
+    measure_deref("ptr", &plain);
+    measure_deref("ref", &borrowed);
+    measure_deref("mixed", &mixed);
+
+    measure_create("&T", &values, |x| *black_box(x));
+    measure_create("ptr", &values, |x| *black_box(ARef::new_ptr(x)));
+    measure_create("ref", &cells, |x| *black_box(ARef::new_ref(x.borrow())));
+}
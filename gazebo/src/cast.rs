@@ -12,24 +12,96 @@
 //! Most of these operations are inherently unsafe, but provided as strongly-typed wrappers
 //! to reduce the chance of typos ending up with even more unsafe functions. If you use the
 //! result in incorrect ways, it will cause undefined behaviour.
+//!
+//! ## Provenance
+//!
+//! A pointer is more than its address: it also carries _provenance_, the permission to
+//! access a particular allocation. Converting a pointer to a `usize` and back loses that
+//! permission unless it is explicitly _exposed_ first.
+//!
+//! * [`addr`], [`with_addr`] and [`map_addr`] keep the provenance of an existing reference
+//!   while inspecting or changing its address. They are sound under strict provenance, and
+//!   pass Miri with `-Zmiri-strict-provenance`. Prefer them for new code.
+//! * [`expose_provenance`] and [`with_exposed_provenance`] round-trip through a plain
+//!   `usize`. They are sound under the exposed provenance model, but Miri's strict
+//!   provenance checks reject [`with_exposed_provenance`].
+//! * [`ptr_to_usize`] and [`usize_to_ptr`] are the older names for the exposed provenance
+//!   functions, and behave identically.
+//!
+//! All the other functions here only reinterpret the pointee, so provenance is unaffected.
 
 // These are inherently unsafe in fairly obvious ways. Safety is left entirely to the user.
 // So doc's wouldn't really help.
 #![allow(clippy::missing_safety_doc)]
 
-use std::mem;
-use std::ptr;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem;
+use core::mem::ManuallyDrop;
+use core::ptr;
+use core::slice;
+
+/// The address of a reference, without exposing its provenance, so the result can't
+/// be turned back into a reference by [`with_exposed_provenance`]. Use [`with_addr`]
+/// instead.
+#[inline(always)]
+pub fn addr<T: ?Sized>(x: &T) -> usize {
+    ptr::from_ref(x).addr()
+}
+
+/// A reference with the provenance of `x`, but the address `addr`.
+///
+/// Undefined behaviour if `addr` doesn't point to a valid `T` within the same allocation
+/// as `x`, or does not satisfy the alignment of type `T`.
+///
+/// ```
+/// let xs = [1u32, 2, 3];
+/// let y: &u32 = unsafe { gazebo::cast::with_addr(&xs[0], gazebo::cast::addr(&xs[2])) };
+/// assert_eq!(*y, 3);
+/// ```
+#[inline(always)]
+pub unsafe fn with_addr<T>(x: &T, addr: usize) -> &T {
+    &*ptr::from_ref(x).with_addr(addr)
+}
+
+/// Like [`with_addr`], but computing the new address from the old one.
+///
+/// Undefined behaviour under the same conditions as [`with_addr`].
+#[inline(always)]
+pub unsafe fn map_addr<T>(x: &T, f: impl FnOnce(usize) -> usize) -> &T {
+    &*ptr::from_ref(x).map_addr(f)
+}
 
+/// The address of a reference, exposing its provenance so that
+/// [`with_exposed_provenance`] can later turn the address back into a reference.
+#[inline(always)]
+pub fn expose_provenance<T: ?Sized>(x: &T) -> usize {
+    ptr::from_ref(x).expose_provenance()
+}
+
+/// Turn an address from [`expose_provenance`] back into a reference.
+///
+/// Undefined behaviour if the argument is zero, does not satisfy the alignment of type `T`,
+/// or wasn't derived from the address of a live `T` whose provenance was exposed.
+/// Not supported by Miri's strict provenance checks, use [`with_addr`] where possible.
+#[inline(always)]
+pub unsafe fn with_exposed_provenance<'a, T>(x: usize) -> &'a T {
+    &*ptr::with_exposed_provenance(x)
+}
+
+/// Equivalent to [`expose_provenance`].
 #[inline(always)]
 pub fn ptr_to_usize<T: ?Sized>(x: &T) -> usize {
-    x as *const T as *const () as usize
+    expose_provenance(x)
 }
 
+/// Equivalent to [`with_exposed_provenance`].
+///
 /// Undefined behaviour if the argument is zero, or does not satisfy the alignment
-/// of type `T`.
+/// of type `T`, or wasn't derived from [`ptr_to_usize`] of a live `T`.
 #[inline(always)]
 pub unsafe fn usize_to_ptr<'a, T>(x: usize) -> &'a T {
-    &*(x as *const T)
+    with_exposed_provenance(x)
 }
 
 /// Undefined behaviour if the argument does not satisfy the alignment of type `To`.
@@ -45,11 +117,11 @@ pub unsafe fn ptr_mut<From, To>(x: &mut From) -> &mut To {
 }
 
 #[inline(always)]
-pub unsafe fn ptr_lifetime<'a, 'b, T: ?Sized>(x: &'a T) -> &'b T {
+pub unsafe fn ptr_lifetime<'b, T: ?Sized>(x: &T) -> &'b T {
     &*(x as *const T)
 }
 
-/// Like normal [`transmute`](std::mem::transmute), but without the compile-time
+/// Like normal [`transmute`](core::mem::transmute), but without the compile-time
 /// check that the sizes of the input and output are the same. Despite the removal
 /// of the compile-time check, this property _must still hold_, and there is
 /// a [`debug_assert_eq`] which checks that. All the rules and warnings from
@@ -66,12 +138,345 @@ pub unsafe fn transmute_unchecked<A, B>(x: A) -> B {
     b
 }
 
+/// Like [`transmute_unchecked`], but the sizes of `A` and `B` are checked at compile
+/// time (when the function is instantiated) rather than at runtime. Alignment doesn't
+/// matter for values, only for the pointer casts below. All the rules and warnings from
+/// [`transmute`](core::mem::transmute) still apply.
+///
+/// ```compile_fail,E0080
+/// fn bad<T>(x: T) -> u64 {
+///     unsafe { gazebo::cast::transmute_checked(x) }
+/// }
+/// bad(1u32);
+/// ```
+#[inline]
+pub unsafe fn transmute_checked<A, B>(x: A) -> B {
+    const {
+        assert!(
+            mem::size_of::<A>() == mem::size_of::<B>(),
+            "transmute_checked: types must have the same size"
+        )
+    };
+    #[repr(C)]
+    union Transmute<A, B> {
+        a: ManuallyDrop<A>,
+        b: ManuallyDrop<B>,
+    }
+    ManuallyDrop::into_inner(
+        Transmute {
+            a: ManuallyDrop::new(x),
+        }
+        .b,
+    )
+}
+
+/// Reinterpret a slice of `A` as a slice of `B`, adjusting the length so the result covers
+/// the same bytes. `B` must not be zero-sized, which is checked at compile time.
+/// Panics if the slice isn't aligned for `B`, or its size isn't a multiple of the size of `B`.
+///
+/// Undefined behaviour if the bytes are not valid values of `B`.
+#[inline]
+pub unsafe fn slice<A, B>(x: &[A]) -> &[B] {
+    let len = slice_len::<A, B>(x.as_ptr().cast(), x.len());
+    slice::from_raw_parts(x.as_ptr().cast(), len)
+}
+
+/// Like [`slice`](fn@slice), but for mutable slices.
+///
+/// Undefined behaviour if the bytes are not valid values of `B`, or values of `B`
+/// written through the result are not valid values of `A`.
+#[inline]
+pub unsafe fn slice_mut<A, B>(x: &mut [A]) -> &mut [B] {
+    let len = slice_len::<A, B>(x.as_ptr().cast(), x.len());
+    slice::from_raw_parts_mut(x.as_mut_ptr().cast(), len)
+}
+
+#[inline]
+fn slice_len<A, B>(ptr: *const B, len: usize) -> usize {
+    const {
+        assert!(
+            mem::size_of::<B>() != 0,
+            "cast::slice: target type is zero-sized"
+        )
+    };
+    assert!(
+        ptr.is_aligned(),
+        "cast::slice: pointer is not aligned for the target type"
+    );
+    let bytes = len * mem::size_of::<A>();
+    assert!(
+        bytes.is_multiple_of(mem::size_of::<B>()),
+        "cast::slice: size is not a multiple of the target type size"
+    );
+    bytes / mem::size_of::<B>()
+}
+
+/// Reinterpret a [`Box`] of `A` as a [`Box`] of `B`. The types must have the same size
+/// and alignment (so the allocation can be freed), which is checked at compile time.
+///
+/// Undefined behaviour if the value is not a valid `B`.
+///
+/// ```compile_fail,E0080
+/// let _: Box<u64> = unsafe { gazebo::cast::boxed(Box::new([0u32; 2])) };
+/// ```
+#[inline]
+pub unsafe fn boxed<A, B>(x: Box<A>) -> Box<B> {
+    const {
+        assert!(
+            mem::size_of::<A>() == mem::size_of::<B>(),
+            "cast::boxed: types must have the same size"
+        );
+        assert!(
+            mem::align_of::<A>() == mem::align_of::<B>(),
+            "cast::boxed: types must have the same alignment"
+        );
+    };
+    Box::from_raw(Box::into_raw(x).cast())
+}
+
+/// Reinterpret a [`Vec`] of `A` as a [`Vec`] of `B`, adjusting the length and capacity so
+/// they cover the same bytes. The types must have the same alignment (so the allocation
+/// can be freed) and not be zero-sized, which is checked at compile time.
+/// Panics if the length or capacity in bytes isn't a multiple of the size of `B`.
+///
+/// Undefined behaviour if the values are not valid values of `B`.
+///
+/// ```compile_fail,E0080
+/// let _: Vec<u16> = unsafe { gazebo::cast::vec(vec![0u8; 2]) };
+/// ```
+pub unsafe fn vec<A, B>(x: Vec<A>) -> Vec<B> {
+    const {
+        assert!(
+            mem::align_of::<A>() == mem::align_of::<B>(),
+            "cast::vec: types must have the same alignment"
+        );
+        assert!(
+            mem::size_of::<A>() != 0 && mem::size_of::<B>() != 0,
+            "cast::vec: types must not be zero-sized"
+        );
+    };
+    let len = x.len() * mem::size_of::<A>();
+    let cap = x.capacity() * mem::size_of::<A>();
+    assert!(
+        len.is_multiple_of(mem::size_of::<B>()) && cap.is_multiple_of(mem::size_of::<B>()),
+        "cast::vec: length and capacity must be a multiple of the target type size"
+    );
+    let mut x = ManuallyDrop::new(x);
+    Vec::from_raw_parts(
+        x.as_mut_ptr().cast(),
+        len / mem::size_of::<B>(),
+        cap / mem::size_of::<B>(),
+    )
+}
+
+/// Plain old data: types where every bit pattern is a valid value, and which have no
+/// padding bytes, so they can be viewed as bytes (and bytes viewed as them) without
+/// any `unsafe`. Used by [`bytes_of`], [`from_bytes`] and friends.
+///
+/// Implemented for the integer and float types, and arrays of `Pod` values. Not
+/// implemented for `bool` or `char`, since not all bit patterns are valid values.
+///
+/// Use `#[derive(Pod)]` for your own structs, which checks the struct is `#[repr(C)]`
+/// or `#[repr(transparent)]`, that all the fields are `Pod`, and that there is no padding.
+///
+/// ```
+/// use gazebo::cast::Pod;
+///
+/// #[derive(Clone, Copy, Pod)]
+/// #[repr(C)]
+/// struct Point {
+///     x: u32,
+///     y: u32,
+/// }
+///
+/// let p = Point { x: 1, y: 2 };
+/// assert_eq!(gazebo::cast::bytes_of(&p).len(), 8);
+/// ```
+///
+/// Structs with padding are rejected:
+///
+/// ```compile_fail,E0080
+/// use gazebo::cast::Pod;
+///
+/// #[derive(Clone, Copy, Pod)]
+/// #[repr(C)]
+/// struct Padded {
+///     x: u8,
+///     y: u32,
+/// }
+/// ```
+///
+/// Including padding inside a generic field, which is checked when the outer struct is:
+///
+/// ```compile_fail,E0080
+/// use gazebo::cast::Pod;
+///
+/// #[derive(Clone, Copy, Pod)]
+/// #[repr(C)]
+/// struct Pair<A, B> {
+///     a: A,
+///     b: B,
+/// }
+///
+/// #[derive(Clone, Copy, Pod)]
+/// #[repr(C)]
+/// struct Outer {
+///     p: Pair<u8, u32>,
+/// }
+/// ```
+///
+/// As are fields which are not `Pod`:
+///
+/// ```compile_fail,E0277
+/// use gazebo::cast::Pod;
+///
+/// #[derive(Clone, Copy, Pod)]
+/// #[repr(C)]
+/// struct Flag {
+///     x: bool,
+/// }
+/// ```
+///
+/// # Safety
+///
+/// Implementors must have no padding, no pointers or references, and every bit pattern
+/// must be a valid value. Prefer the derive, which checks these properties.
+pub unsafe trait Pod: Copy + 'static {
+    /// Evaluated whenever the byte view functions are instantiated, so the derive can
+    /// check generic types for padding.
+    #[doc(hidden)]
+    const ASSERT_NO_PADDING: () = ();
+}
+
+macro_rules! impl_pod {
+    ($($t:ty)*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(() u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize f32 f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {
+    const ASSERT_NO_PADDING: () = T::ASSERT_NO_PADDING;
+}
+
+pub use gazebo_derive::Pod;
+
+/// View a [`Pod`] value as its bytes.
+#[inline]
+pub fn bytes_of<T: Pod>(x: &T) -> &[u8] {
+    bytes_of_slice(slice::from_ref(x))
+}
+
+/// View a [`Pod`] value as its bytes, which may be modified.
+#[inline]
+pub fn bytes_of_mut<T: Pod>(x: &mut T) -> &mut [u8] {
+    bytes_of_slice_mut(slice::from_mut(x))
+}
+
+/// View a slice of [`Pod`] values as their bytes.
+#[inline]
+pub fn bytes_of_slice<T: Pod>(x: &[T]) -> &[u8] {
+    const { T::ASSERT_NO_PADDING };
+    // SAFETY: `Pod` types have no padding, so every byte is initialised.
+    unsafe { slice::from_raw_parts(x.as_ptr().cast(), mem::size_of_val(x)) }
+}
+
+/// View a slice of [`Pod`] values as their bytes, which may be modified.
+#[inline]
+pub fn bytes_of_slice_mut<T: Pod>(x: &mut [T]) -> &mut [u8] {
+    const { T::ASSERT_NO_PADDING };
+    // SAFETY: `Pod` types have no padding, and any bytes written are a valid `T`.
+    unsafe { slice::from_raw_parts_mut(x.as_mut_ptr().cast(), mem::size_of_val(x)) }
+}
+
+/// View bytes as a [`Pod`] value. Returns [`None`] if the length isn't the size of `T`,
+/// or the bytes aren't aligned for `T` (see [`read_from_bytes`] for unaligned data).
+///
+/// ```
+/// let x = 0x01020304u32;
+/// let bytes = gazebo::cast::bytes_of(&x);
+/// assert_eq!(gazebo::cast::from_bytes::<u32>(bytes), Some(&x));
+/// assert_eq!(gazebo::cast::from_bytes::<u32>(&bytes[1..]), None);
+/// ```
+#[inline]
+pub fn from_bytes<T: Pod>(x: &[u8]) -> Option<&T> {
+    const { T::ASSERT_NO_PADDING };
+    let p = x.as_ptr().cast::<T>();
+    if x.len() == mem::size_of::<T>() && p.is_aligned() {
+        // SAFETY: The size and alignment match and every bit pattern is a valid `T`.
+        Some(unsafe { &*p })
+    } else {
+        None
+    }
+}
+
+/// Like [`from_bytes`], but for mutable bytes.
+#[inline]
+pub fn from_bytes_mut<T: Pod>(x: &mut [u8]) -> Option<&mut T> {
+    const { T::ASSERT_NO_PADDING };
+    let p = x.as_mut_ptr().cast::<T>();
+    if x.len() == mem::size_of::<T>() && p.is_aligned() {
+        // SAFETY: The size and alignment match and every bit pattern is a valid `T`.
+        Some(unsafe { &mut *p })
+    } else {
+        None
+    }
+}
+
+/// Copy bytes into a [`Pod`] value, which works regardless of alignment. Returns [`None`]
+/// if the length isn't the size of `T`.
+#[inline]
+pub fn read_from_bytes<T: Pod>(x: &[u8]) -> Option<T> {
+    const { T::ASSERT_NO_PADDING };
+    if x.len() == mem::size_of::<T>() {
+        // SAFETY: The size matches and every bit pattern is a valid `T`.
+        Some(unsafe { ptr::read_unaligned(x.as_ptr().cast()) })
+    } else {
+        None
+    }
+}
+
+/// View bytes as a slice of [`Pod`] values. Returns [`None`] if the length isn't a
+/// multiple of the size of `T`, or the bytes aren't aligned for `T`. `T` must not be
+/// zero-sized, which is checked at compile time.
+#[inline]
+pub fn slice_from_bytes<T: Pod>(x: &[u8]) -> Option<&[T]> {
+    let len = slice_from_bytes_len::<T>(x.as_ptr().cast(), x.len())?;
+    // SAFETY: The size and alignment match and every bit pattern is a valid `T`.
+    Some(unsafe { slice::from_raw_parts(x.as_ptr().cast(), len) })
+}
+
+/// Like [`slice_from_bytes`], but for mutable bytes.
+#[inline]
+pub fn slice_from_bytes_mut<T: Pod>(x: &mut [u8]) -> Option<&mut [T]> {
+    let len = slice_from_bytes_len::<T>(x.as_ptr().cast(), x.len())?;
+    // SAFETY: The size and alignment match and every bit pattern is a valid `T`.
+    Some(unsafe { slice::from_raw_parts_mut(x.as_mut_ptr().cast(), len) })
+}
+
+#[inline]
+fn slice_from_bytes_len<T: Pod>(ptr: *const T, len: usize) -> Option<usize> {
+    const {
+        let () = T::ASSERT_NO_PADDING;
+        assert!(
+            mem::size_of::<T>() != 0,
+            "cast::slice_from_bytes: target type is zero-sized"
+        )
+    };
+    if ptr.is_aligned() && len.is_multiple_of(mem::size_of::<T>()) {
+        Some(len / mem::size_of::<T>())
+    } else {
+        None
+    }
+}
+
 #[macro_export]
-/// `transmute!(from-type, to-type, value)` will do a [`transmute`](std::mem::transmute),
+/// `transmute!(from-type, to-type, value)` will do a [`transmute`](core::mem::transmute),
 /// but the original and result types must be specified.
 macro_rules! transmute {
     ($from:ty, $to:ty, $e:expr) => {
-        std::mem::transmute::<$from, $to>($e)
+        ::core::mem::transmute::<$from, $to>($e)
     };
 }
 
@@ -80,6 +485,30 @@ mod tests {
     use std::cell::Cell;
 
     use super::*;
+    #[allow(unused_imports)] // Not actually unused, this makes testing the derive macro work
+    use crate as gazebo;
+
+    #[test]
+    fn test_with_addr() {
+        let xs = [1u64, 2, 3, 4];
+        let x = &xs[1];
+        assert_eq!(addr(x), addr(&xs) + 8);
+        let y: &u64 = unsafe { with_addr(x, addr(&xs[3])) };
+        assert_eq!(*y, 4);
+        let z: &u64 = unsafe { map_addr(y, |a| a - 24) };
+        assert_eq!(*z, 1);
+    }
+
+    // Miri's strict provenance checks reject integer to pointer casts by design.
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_exposed_provenance() {
+        let x = "test".to_owned();
+        let p = expose_provenance(&x);
+        assert_eq!(p, addr(&x));
+        assert_eq!(unsafe { with_exposed_provenance::<String>(p) }, "test");
+        assert_eq!(unsafe { usize_to_ptr::<String>(ptr_to_usize(&x)) }, "test");
+    }
 
     #[test]
     fn test_transmute() {
@@ -129,6 +558,80 @@ mod tests {
         assert_eq!(x, y)
     }
 
+    #[test]
+    fn test_transmute_checked() {
+        fn magic<'a, T>(x: T) -> Foo<'a, Vec<u8>> {
+            unsafe { transmute_checked(x) }
+        }
+
+        let s = "magic".to_owned();
+        let c = Cell::new(8);
+        let output = magic(Foo(&c, &s));
+        assert_eq!(c.get(), 8);
+        assert_eq!(output.1, b"magic");
+        mem::drop(output);
+        assert_eq!(c.get(), 7);
+
+        let x: [u8; 8] = unsafe { transmute_checked(0x0102030405060708u64) };
+        assert_eq!(u64::from_ne_bytes(x), 0x0102030405060708);
+    }
+
+    #[test]
+    fn test_slice() {
+        let xs: [u32; 2] = [1, 2];
+        let ys: &[u8] = unsafe { slice(&xs) };
+        assert_eq!(ys.len(), 8);
+        let zs: &[u32] = unsafe { slice(ys) };
+        assert_eq!(zs, [1, 2]);
+        let ws: &[[u32; 2]] = unsafe { slice(zs) };
+        assert_eq!(ws, [[1, 2]]);
+
+        let mut xs: [u16; 4] = [0; 4];
+        let ys: &mut [u64] = unsafe { slice_mut(&mut xs) };
+        ys[0] = u64::MAX;
+        assert_eq!(xs, [u16::MAX; 4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_slice_wrong_length() {
+        let xs: [u8; 6] = [0; 6];
+        let _: &[[u8; 4]] = unsafe { slice(&xs) };
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_slice_wrong_alignment() {
+        let xs: [u32; 4] = [0; 4];
+        let ys: &[u8] = unsafe { slice(&xs) };
+        let _: &[u32] = unsafe { slice(&ys[1..5]) };
+    }
+
+    #[test]
+    fn test_boxed() {
+        let x: Box<f32> = unsafe { boxed(Box::new(0x3f800000u32)) };
+        assert_eq!(*x, 1.0);
+    }
+
+    #[test]
+    fn test_vec() {
+        let mut xs: Vec<u32> = Vec::with_capacity(4);
+        xs.extend([1, 2]);
+        let ys: Vec<[u32; 2]> = unsafe { vec(xs) };
+        assert_eq!(ys, [[1, 2]]);
+        assert_eq!(ys.capacity(), 2);
+        let mut zs: Vec<u32> = unsafe { vec(ys) };
+        zs.push(3);
+        assert_eq!(zs, [1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_vec_wrong_capacity() {
+        let xs: Vec<u32> = Vec::with_capacity(3);
+        let _: Vec<[u32; 2]> = unsafe { vec(xs) };
+    }
+
     #[test]
     #[cfg(debug_assertions)]
     #[should_panic]
@@ -143,4 +646,56 @@ mod tests {
         let xs: [u8; 8] = unsafe { transmute_unchecked(0u64) };
         let _: u32 = unsafe { transmute_unchecked(&xs[1..5]) };
     }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Pod)]
+    #[repr(C)]
+    struct Header {
+        tag: u32,
+        len: u16,
+        flags: [u8; 2],
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Pod)]
+    #[repr(transparent)]
+    struct Wrapper<T>(T);
+
+    #[test]
+    fn test_pod_bytes() {
+        let mut h = Header {
+            tag: 1,
+            len: 2,
+            flags: [3, 4],
+        };
+        assert_eq!(bytes_of(&h).len(), 8);
+        assert_eq!(from_bytes::<Header>(bytes_of(&h)), Some(&h));
+        assert_eq!(read_from_bytes::<Header>(bytes_of(&h)), Some(h));
+        bytes_of_mut(&mut h)[6] = 9;
+        assert_eq!(h.flags, [9, 4]);
+
+        let mut w = Wrapper(7u64);
+        *from_bytes_mut::<Wrapper<u64>>(bytes_of_mut(&mut w)).unwrap() = Wrapper(8);
+        assert_eq!(w, Wrapper(8));
+    }
+
+    #[test]
+    fn test_pod_bad_bytes() {
+        let xs = [0u32; 4];
+        let bytes = bytes_of(&xs);
+        assert_eq!(from_bytes::<u32>(&bytes[..3]), None);
+        assert_eq!(from_bytes::<u32>(&bytes[1..5]), None);
+        assert_eq!(read_from_bytes::<u32>(&bytes[1..5]), Some(0));
+        assert_eq!(read_from_bytes::<u32>(&bytes[1..4]), None);
+        assert_eq!(slice_from_bytes::<u32>(&bytes[1..]), None);
+        assert_eq!(slice_from_bytes::<u32>(&bytes[..15]), None);
+    }
+
+    #[test]
+    fn test_pod_slice() {
+        let mut xs = [1u16, 2, 3, 4];
+        assert_eq!(bytes_of_slice(&xs).len(), 8);
+        let ys: &[u32] = slice_from_bytes(bytes_of_slice(&xs)).unwrap();
+        assert_eq!(ys.len(), 2);
+        slice_from_bytes_mut::<u16>(bytes_of_slice_mut(&mut xs)).unwrap()[0] = 5;
+        assert_eq!(xs, [5, 2, 3, 4]);
+    }
 }
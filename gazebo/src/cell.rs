@@ -10,127 +10,422 @@
 //! Additions to the [`Ref`] mechanism.
 
 // We used to implement `ARef` as an enum of `{Ptr(&'a T), Ref(Ref<'a, T>)}`.
-// That works, but consumes 3 words and requires a branch on every access of the underlying
-// pointer. We can optimise that by relying on the underlying details of `Ref`, which is
-// (currently) defined as (after a bit of inlining):
+// That works, but requires a branch on every access of the underlying pointer.
+// Instead an `ARef` is a pointer plus an `Option<&Cell<isize>>`, the borrow flag
+// of the `RefCell` if we hold a shared borrow of it. That makes `ARef` two words
+// for sized types, `deref` a single load whichever the representation, and
+// `new_ptr` just stores the pointer.
 //
-// ```
-// pub struct Ref<'a, T: ?Sized + 'a> {
-//    value: &'a T,
-//    borrow: &'a Cell<isize>,
-// }
-// ```
+// `RefCell` doesn't expose its borrow flag, but a `Ref<()>` is a pointer to the
+// value and a reference to the flag. We map the `Ref` to a pointer we know, find
+// the flag as the other word (checking the pointer is where we expect), and from
+// then on release and duplicate the borrow ourselves, exactly as `Ref` would.
 //
-// Because the pointer must always be non-null, we can switch that out for:
-//
-// ```
-// pub struct ARef<'a, T: ?Sized + 'a> {
-//    value: &'a T,
-//    borrow: Option<&'a Cell<isize>>,
-// }
-// ```
-//
-// And use `None` to represent the `Ptr` case. We do that with transmute trickery,
-// but write some good tests that will break if the representation changes,
-// and if necessary we can always switch to the enum representation.
-
-use std::cell::BorrowError;
-use std::cell::Ref;
-use std::cell::RefCell;
-use std::cmp::Ordering;
-use std::fmt;
-use std::fmt::Display;
-use std::hash::Hash;
-use std::hash::Hasher;
-use std::ops::Deref;
+// With the `track_borrows` feature, borrows record their `#[track_caller]` location in
+// a per-thread table keyed by the address of the `RefCell`, which is reported when a
+// borrow fails. The recorded sites are `AsARef` on a `RefCell`, `AsARefMut` on a
+// `&RefCell`, and `RcRef::new`/`RcRefMut::new` and their `try_` variants, along with
+// anything cloned, mapped or split from those. `ARef::new_ref` and `ARefMut::new_ref`
+// are given a guard without its `RefCell`, so can't be recorded.
 
-#[derive(Debug)]
-enum ARefImpl<'a, T: ?Sized + 'a> {
-    Ptr(&'a T),
-    Ref(Ref<'a, T>),
+use alloc::rc::Rc;
+use core::cell::BorrowError;
+use core::cell::BorrowMutError;
+use core::cell::Ref;
+use core::cell::RefCell;
+use core::cell::RefMut;
+use core::cmp::Ordering;
+use core::fmt;
+use core::fmt::Display;
+use core::hash::Hash;
+use core::hash::Hasher;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::ptr::NonNull;
+
+use dupe::Dupe;
+
+mod aref_impl {
+    use core::cell::Cell;
+    use core::cell::Ref;
+    use core::fmt;
+    use core::fmt::Debug;
+    use core::marker::PhantomData;
+    use core::mem;
+    use core::mem::ManuallyDrop;
+    use core::ptr::NonNull;
+
+    /// The count of shared borrows kept by a `RefCell`, which every `Ref` points at.
+    type BorrowFlag = Cell<isize>;
+
+    /// A byte whose address can't be the address of a `BorrowFlag`.
+    static MARKER: u8 = 0;
+
+    /// Take the borrow flag out of a `Ref`, leaving the `RefCell` borrowed.
+    fn into_borrow_flag<'a, T: ?Sized>(x: Ref<'a, T>) -> &'a BorrowFlag {
+        let marker = (&MARKER as *const u8).cast::<()>();
+        // SAFETY: a `()` can live at any non-null address.
+        let x = Ref::map(x, |_| unsafe { &*marker });
+        // A `Ref<()>` is the pointer we just gave it and a reference to the borrow flag, in
+        // some order. `transmute` checks the size at compile time, and we check the order.
+        // SAFETY: both words are pointers, so have no padding.
+        let words: [*const (); 2] = unsafe { mem::transmute(x) };
+        let flag = if words[0] == marker {
+            words[1]
+        } else {
+            words[0]
+        };
+        assert!(
+            words.contains(&marker) && flag != marker,
+            "unexpected layout of `Ref`"
+        );
+        // SAFETY: the other word of a `Ref` is the flag, which lives as long as the `RefCell`.
+        let flag = unsafe { &*flag.cast::<BorrowFlag>() };
+        debug_assert!(
+            flag.get() > 0,
+            "`Ref` doesn't point at a shared borrow count"
+        );
+        flag
+    }
+
+    pub(super) struct ARefImpl<'a, T: ?Sized + 'a> {
+        value: NonNull<T>,
+        /// The flag of the `RefCell` we hold a shared borrow of, `None` for a plain pointer.
+        borrow: Option<&'a BorrowFlag>,
+        _marker: PhantomData<&'a T>,
+    }
+
+    impl<T: ?Sized> Drop for ARefImpl<'_, T> {
+        #[inline]
+        fn drop(&mut self) {
+            if let Some(flag) = self.borrow {
+                // The same as dropping a `Ref`.
+                flag.set(flag.get() - 1);
+            }
+        }
+    }
+
+    impl<T: Debug + ?Sized> Debug for ARefImpl<'_, T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("ARefImpl").field(&self.get()).finish()
+        }
+    }
+
+    impl<'a, T: ?Sized + 'a> ARefImpl<'a, T> {
+        #[inline]
+        fn from_parts<U: ?Sized>(
+            value: NonNull<U>,
+            borrow: Option<&'a BorrowFlag>,
+        ) -> ARefImpl<'a, U> {
+            ARefImpl {
+                value,
+                borrow,
+                _marker: PhantomData,
+            }
+        }
+
+        /// Give up ownership of the borrow, if any, without releasing it.
+        #[inline]
+        fn into_borrow(self) -> Option<&'a BorrowFlag> {
+            ManuallyDrop::new(self).borrow
+        }
+
+        #[inline]
+        pub(super) fn new_ptr(x: &'a T) -> Self {
+            Self::from_parts(NonNull::from(x), None)
+        }
+
+        #[inline]
+        pub(super) fn new_ref(x: Ref<'a, T>) -> Self {
+            // The value lives in the `RefCell`, so stays put while we hold the borrow.
+            let value = NonNull::from(&*x);
+            Self::from_parts(value, Some(into_borrow_flag(x)))
+        }
+
+        #[inline]
+        pub(super) fn get(&self) -> &T {
+            // SAFETY: `value` is valid for `'a`, and if it points into a `RefCell`
+            // we hold a shared borrow of it.
+            unsafe { self.value.as_ref() }
+        }
+
+        /// Take another shared borrow of the same `RefCell`, if any.
+        #[inline]
+        fn duplicate_borrow(&self) -> Option<&'a BorrowFlag> {
+            if let Some(flag) = self.borrow {
+                // The same as `Ref::clone`.
+                let count = flag.get();
+                assert!(count != isize::MAX, "too many `RefCell` borrows");
+                flag.set(count + 1);
+            }
+            self.borrow
+        }
+
+        #[inline]
+        pub(super) fn clone(&self) -> Self {
+            Self::from_parts(self.value, self.duplicate_borrow())
+        }
+
+        #[inline]
+        pub(super) fn map<U: ?Sized, F>(self, f: F) -> ARefImpl<'a, U>
+        where
+            F: FnOnce(&T) -> &U,
+        {
+            let value = NonNull::from(f(self.get()));
+            Self::from_parts(value, self.into_borrow())
+        }
+
+        #[inline]
+        pub(super) fn map_split<U: ?Sized, V: ?Sized, F>(
+            self,
+            f: F,
+        ) -> (ARefImpl<'a, U>, ARefImpl<'a, V>)
+        where
+            F: FnOnce(&T) -> (&U, &V),
+        {
+            let (u, v) = f(self.get());
+            let (u, v) = (NonNull::from(u), NonNull::from(v));
+            let u = Self::from_parts(u, self.duplicate_borrow());
+            (u, Self::from_parts(v, self.into_borrow()))
+        }
+
+        #[inline]
+        pub(super) fn filter_map<U: ?Sized, F>(self, f: F) -> Result<ARefImpl<'a, U>, Self>
+        where
+            F: FnOnce(&T) -> Option<&U>,
+        {
+            match f(self.get()).map(NonNull::from) {
+                Some(value) => Ok(Self::from_parts(value, self.into_borrow())),
+                None => Err(self),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "track_borrows")]
+mod track {
+    use std::cell::Cell;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::fmt::Write;
+    use std::panic::Location;
+    use std::string::String;
+    use std::vec::Vec;
+
+    /// Where an outstanding borrow of a [`RefCell`] was taken, as recorded with the
+    /// `track_borrows` feature.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct BorrowSite {
+        /// The caller which took the borrow.
+        pub location: &'static Location<'static>,
+        /// Whether the borrow is exclusive.
+        pub mutable: bool,
+    }
+
+    impl fmt::Display for BorrowSite {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let kind = if self.mutable { "mutable" } else { "shared" };
+            write!(f, "{} borrow at {}", kind, self.location)
+        }
+    }
+
+    thread_local! {
+        static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+        static BORROWS: RefCell<HashMap<usize, Vec<(u64, BorrowSite)>>> =
+            RefCell::new(HashMap::new());
+    }
+
+    fn cell_key<T: ?Sized>(cell: &RefCell<T>) -> usize {
+        crate::cast::addr(cell)
+    }
+
+    /// Removes its entry from the table of outstanding borrows when dropped.
+    #[derive(Debug)]
+    pub(super) struct BorrowTrack(Option<(usize, u64, BorrowSite)>);
+
+    impl BorrowTrack {
+        #[inline]
+        pub(super) fn none() -> Self {
+            BorrowTrack(None)
+        }
+
+        #[track_caller]
+        pub(super) fn new<T: ?Sized>(cell: &RefCell<T>, mutable: bool) -> Self {
+            let site = BorrowSite {
+                location: Location::caller(),
+                mutable,
+            };
+            Self::register(cell_key(cell), site)
+        }
+
+        fn register(key: usize, site: BorrowSite) -> Self {
+            let id = NEXT_ID.with(|x| {
+                let id = x.get();
+                x.set(id + 1);
+                id
+            });
+            BORROWS.with(|x| x.borrow_mut().entry(key).or_default().push((id, site)));
+            BorrowTrack(Some((key, id, site)))
+        }
+
+        /// Record another borrow from the same site, e.g. for `Ref::clone`.
+        pub(super) fn duplicate(&self) -> Self {
+            match self.0 {
+                None => BorrowTrack(None),
+                Some((key, _, site)) => Self::register(key, site),
+            }
+        }
+    }
+
+    impl Drop for BorrowTrack {
+        fn drop(&mut self) {
+            if let Some((key, id, _)) = self.0 {
+                // If the thread is shutting down the table may already be gone.
+                let _ignore = BORROWS.try_with(|x| {
+                    let mut x = x.borrow_mut();
+                    if let Some(sites) = x.get_mut(&key) {
+                        sites.retain(|(i, _)| *i != id);
+                        if sites.is_empty() {
+                            x.remove(&key);
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    /// The outstanding borrows of `cell` taken through the `gazebo::cell` APIs, oldest first.
+    ///
+    /// Borrows are recorded by [`AsARef`](super::AsARef) on a [`RefCell`],
+    /// [`AsARefMut`](super::AsARefMut) on a `&RefCell`, [`RcRef`](super::RcRef) and
+    /// [`RcRefMut`](super::RcRefMut), and carried over by their `clone`, `map`, `map_split`
+    /// and `filter_map`. Borrows taken directly with [`RefCell::borrow`] or
+    /// [`RefCell::borrow_mut`], including those wrapped with [`ARef::new_ref`](super::ARef::new_ref)
+    /// or [`ARefMut::new_ref`](super::ARefMut::new_ref), are not recorded.
+    pub fn borrow_sites<T: ?Sized>(cell: &RefCell<T>) -> Vec<BorrowSite> {
+        BORROWS.with(|x| {
+            x.borrow()
+                .get(&cell_key(cell))
+                .map_or_else(Vec::new, |sites| sites.iter().map(|x| x.1).collect())
+        })
+    }
+
+    pub(super) fn describe_borrows<T: ?Sized>(cell: &RefCell<T>) -> String {
+        let mut res = String::new();
+        for site in borrow_sites(cell) {
+            let _ignore = write!(res, "\n  outstanding {}", site);
+        }
+        res
+    }
+}
+
+#[cfg(not(feature = "track_borrows"))]
+mod track {
+    use core::cell::RefCell;
+
+    #[derive(Debug)]
+    pub(super) struct BorrowTrack;
+
+    impl BorrowTrack {
+        #[inline]
+        pub(super) fn none() -> Self {
+            BorrowTrack
+        }
+
+        #[inline]
+        pub(super) fn new<T: ?Sized>(_cell: &RefCell<T>, _mutable: bool) -> Self {
+            BorrowTrack
+        }
+
+        #[inline]
+        pub(super) fn duplicate(&self) -> Self {
+            BorrowTrack
+        }
+    }
+
+    pub(super) fn describe_borrows<T: ?Sized>(_cell: &RefCell<T>) -> &'static str {
+        ""
+    }
 }
 
+use aref_impl::ARefImpl;
+#[cfg(feature = "track_borrows")]
+pub use track::borrow_sites;
+use track::describe_borrows;
+#[cfg(feature = "track_borrows")]
+pub use track::BorrowSite;
+use track::BorrowTrack;
+
 /// A [`Ref`] that might not actually be borrowed.
 /// Either a `Ptr` (a normal & style reference), or a `Ref` (like from
 /// [`RefCell`]), but exposes all the methods available on [`Ref`].
 #[derive(Debug)]
-pub struct ARef<'a, T: ?Sized + 'a>(ARefImpl<'a, T>);
+pub struct ARef<'a, T: ?Sized + 'a>(ARefImpl<'a, T>, BorrowTrack);
 
 impl<T: ?Sized> Deref for ARef<'_, T> {
     type Target = T;
 
+    #[inline]
     fn deref(&self) -> &T {
-        match &self.0 {
-            ARefImpl::Ptr(p) => p,
-            ARefImpl::Ref(r) => r.deref(),
-        }
+        self.0.get()
     }
 }
 
 impl<'a, T: ?Sized + 'a> ARef<'a, T> {
     /// Create a new [`ARef`] from a pointer.
+    #[inline]
     pub fn new_ptr(x: &'a T) -> Self {
-        ARef(ARefImpl::Ptr(x))
+        ARef(ARefImpl::new_ptr(x), BorrowTrack::none())
     }
 
-    /// Create a new [`ARef`] from a reference.
+    /// Create a new [`ARef`] from a reference. The [`RefCell`] isn't available, so
+    /// with the `track_borrows` feature the borrow isn't recorded, unlike [`AsARef`].
+    #[inline]
     pub fn new_ref(x: Ref<'a, T>) -> Self {
-        ARef(ARefImpl::Ref(x))
+        ARef(ARefImpl::new_ref(x), BorrowTrack::none())
     }
 
     /// See [`Ref.clone`](Ref::clone). Not a self method since that interferes with the [`Deref`].
     #[allow(clippy::should_implement_trait)]
+    #[inline]
     pub fn clone(orig: &Self) -> Self {
-        match &orig.0 {
-            ARefImpl::Ptr(p) => ARef(ARefImpl::Ptr(p)),
-            ARefImpl::Ref(r) => ARef(ARefImpl::Ref(Ref::clone(r))),
-        }
+        ARef(orig.0.clone(), orig.1.duplicate())
     }
 
     /// See [`Ref.map`](Ref::map). Not a self method since that interferes with the [`Deref`].
+    #[inline]
     pub fn map<U: ?Sized, F>(orig: ARef<'a, T>, f: F) -> ARef<'a, U>
     where
         F: FnOnce(&T) -> &U,
     {
-        match orig.0 {
-            ARefImpl::Ptr(p) => ARef(ARefImpl::Ptr(f(p))),
-            ARefImpl::Ref(r) => ARef(ARefImpl::Ref(Ref::map(r, f))),
-        }
+        ARef(orig.0.map(f), orig.1)
     }
 
     /// See [`Ref.map_split`](Ref::map_split). Not a self method since that interferes with the
     /// [`Deref`].
+    #[inline]
     pub fn map_split<U: ?Sized, V: ?Sized, F>(orig: ARef<'a, T>, f: F) -> (ARef<'a, U>, ARef<'a, V>)
     where
         F: FnOnce(&T) -> (&U, &V),
     {
-        match orig.0 {
-            ARefImpl::Ptr(p) => {
-                let (u, v) = f(p);
-                (ARef(ARefImpl::Ptr(u)), ARef(ARefImpl::Ptr(v)))
-            }
-            ARefImpl::Ref(r) => {
-                let (u, v) = Ref::map_split(r, f);
-                (ARef(ARefImpl::Ref(u)), ARef(ARefImpl::Ref(v)))
-            }
-        }
+        let track = orig.1.duplicate();
+        let (u, v) = orig.0.map_split(f);
+        (ARef(u, track), ARef(v, orig.1))
     }
 
     /// See [`Ref.filter_map`](Ref::filter_map). Not a self method since that interferes with the
     /// [`Deref`].
+    #[inline]
     pub fn filter_map<U: ?Sized, F>(orig: ARef<'a, T>, f: F) -> Result<ARef<'a, U>, Self>
     where
         F: FnOnce(&T) -> Option<&U>,
     {
-        match orig.0 {
-            ARefImpl::Ptr(p) => match f(p) {
-                Some(u) => Ok(ARef(ARefImpl::Ptr(u))),
-                None => Err(ARef(ARefImpl::Ptr(p))),
-            },
-            ARefImpl::Ref(r) => match Ref::filter_map(r, f) {
-                Ok(u) => Ok(ARef(ARefImpl::Ref(u))),
-                Err(r) => Err(ARef(ARefImpl::Ref(r))),
-            },
+        match orig.0.filter_map(f) {
+            Ok(u) => Ok(ARef(u, orig.1)),
+            Err(r) => Err(ARef(r, orig.1)),
         }
     }
 }
@@ -171,22 +466,166 @@ impl<A: Ord + ?Sized> Ord for ARef<'_, A> {
     }
 }
 
+#[derive(Debug)]
+enum ARefMutImpl<'a, T: ?Sized + 'a> {
+    Ptr(&'a mut T),
+    Ref(RefMut<'a, T>),
+}
+
+/// A [`RefMut`] that might not actually be borrowed.
+/// Either a `Ptr` (a normal &mut style reference), or a `Ref` (like from
+/// [`RefCell`]), but exposes all the methods available on [`RefMut`].
+#[derive(Debug)]
+pub struct ARefMut<'a, T: ?Sized + 'a>(ARefMutImpl<'a, T>, BorrowTrack);
+
+impl<T: ?Sized> Deref for ARefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match &self.0 {
+            ARefMutImpl::Ptr(p) => p,
+            ARefMutImpl::Ref(r) => r.deref(),
+        }
+    }
+}
+
+impl<T: ?Sized> DerefMut for ARefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        match &mut self.0 {
+            ARefMutImpl::Ptr(p) => p,
+            ARefMutImpl::Ref(r) => r.deref_mut(),
+        }
+    }
+}
+
+impl<'a, T: ?Sized + 'a> ARefMut<'a, T> {
+    /// Create a new [`ARefMut`] from a pointer.
+    pub fn new_ptr(x: &'a mut T) -> Self {
+        ARefMut(ARefMutImpl::Ptr(x), BorrowTrack::none())
+    }
+
+    /// Create a new [`ARefMut`] from a reference. The [`RefCell`] isn't available, so
+    /// with the `track_borrows` feature the borrow isn't recorded, unlike [`AsARefMut`].
+    pub fn new_ref(x: RefMut<'a, T>) -> Self {
+        ARefMut(ARefMutImpl::Ref(x), BorrowTrack::none())
+    }
+
+    /// See [`RefMut.map`](RefMut::map). Not a self method since that interferes with the
+    /// [`Deref`].
+    pub fn map<U: ?Sized, F>(orig: ARefMut<'a, T>, f: F) -> ARefMut<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let track = orig.1;
+        match orig.0 {
+            ARefMutImpl::Ptr(p) => ARefMut(ARefMutImpl::Ptr(f(p)), track),
+            ARefMutImpl::Ref(r) => ARefMut(ARefMutImpl::Ref(RefMut::map(r, f)), track),
+        }
+    }
+
+    /// See [`RefMut.map_split`](RefMut::map_split). Not a self method since that interferes
+    /// with the [`Deref`].
+    pub fn map_split<U: ?Sized, V: ?Sized, F>(
+        orig: ARefMut<'a, T>,
+        f: F,
+    ) -> (ARefMut<'a, U>, ARefMut<'a, V>)
+    where
+        F: FnOnce(&mut T) -> (&mut U, &mut V),
+    {
+        let track = orig.1.duplicate();
+        match orig.0 {
+            ARefMutImpl::Ptr(p) => {
+                let (u, v) = f(p);
+                (
+                    ARefMut(ARefMutImpl::Ptr(u), track),
+                    ARefMut(ARefMutImpl::Ptr(v), orig.1),
+                )
+            }
+            ARefMutImpl::Ref(r) => {
+                let (u, v) = RefMut::map_split(r, f);
+                (
+                    ARefMut(ARefMutImpl::Ref(u), track),
+                    ARefMut(ARefMutImpl::Ref(v), orig.1),
+                )
+            }
+        }
+    }
+
+    /// See [`RefMut.filter_map`](RefMut::filter_map). Not a self method since that interferes
+    /// with the [`Deref`].
+    pub fn filter_map<U: ?Sized, F>(orig: ARefMut<'a, T>, f: F) -> Result<ARefMut<'a, U>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        let track = orig.1;
+        match orig.0 {
+            ARefMutImpl::Ptr(p) => {
+                // The borrow checker can't see that `p` is no longer borrowed when `f`
+                // returns `None`, so go via a raw pointer.
+                let raw: *mut T = p;
+                // SAFETY: `raw` came from a `&'a mut T` which we own.
+                match f(unsafe { &mut *raw }) {
+                    Some(u) => Ok(ARefMut(ARefMutImpl::Ptr(u), track)),
+                    // SAFETY: `f` returned nothing, so no other reference derived from `raw` is live.
+                    None => Err(ARefMut(ARefMutImpl::Ptr(unsafe { &mut *raw }), track)),
+                }
+            }
+            ARefMutImpl::Ref(r) => match RefMut::filter_map(r, f) {
+                Ok(u) => Ok(ARefMut(ARefMutImpl::Ref(u), track)),
+                Err(r) => Err(ARefMut(ARefMutImpl::Ref(r), track)),
+            },
+        }
+    }
+}
+
+impl<T: Display + ?Sized> Display for ARefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        ARefMut::deref(self).fmt(f)
+    }
+}
+
+impl<T: Hash + ?Sized> Hash for ARefMut<'_, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ARefMut::deref(self).hash(state)
+    }
+}
+
+impl<A: PartialEq<B> + ?Sized, B: ?Sized> PartialEq<ARefMut<'_, B>> for ARefMut<'_, A> {
+    fn eq(&self, other: &ARefMut<'_, B>) -> bool {
+        ARefMut::deref(self).eq(ARefMut::deref(other))
+    }
+}
+
+impl<A: Eq + ?Sized> Eq for ARefMut<'_, A> {}
+
+impl<A: PartialOrd<B> + ?Sized, B: ?Sized> PartialOrd<ARefMut<'_, B>> for ARefMut<'_, A> {
+    fn partial_cmp(&self, other: &ARefMut<'_, B>) -> Option<Ordering> {
+        ARefMut::deref(self).partial_cmp(ARefMut::deref(other))
+    }
+}
+
+impl<A: Ord + ?Sized> Ord for ARefMut<'_, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        ARefMut::deref(self).cmp(ARefMut::deref(other))
+    }
+}
+
 /// Obtain an [`ARef`] from either a normal pointer or a [`RefCell`].
 pub trait AsARef<T: ?Sized> {
     /// Get an [`ARef`] pointing at this type.
-    fn as_aref(this: &Self) -> ARef<T>;
+    fn as_aref(this: &Self) -> ARef<'_, T>;
     /// Try and get an [`ARef`] pointing at this type. Returns an [`Err`] if
     /// the type `Self` is a [`RefCell`] which is already mutably borrowed.
-    fn try_as_aref(this: &Self) -> Result<ARef<T>, BorrowError>;
+    fn try_as_aref(this: &Self) -> Result<ARef<'_, T>, BorrowError>;
     /// Return the underlying [`RefCell`] if `Self` is one, otherwise [`None`].
     fn as_ref_cell(this: &Self) -> Option<&RefCell<T>>;
 }
 
 impl<T: ?Sized> AsARef<T> for T {
-    fn as_aref(this: &Self) -> ARef<T> {
+    fn as_aref(this: &Self) -> ARef<'_, T> {
         ARef::new_ptr(this)
     }
-    fn try_as_aref(this: &Self) -> Result<ARef<T>, BorrowError> {
+    fn try_as_aref(this: &Self) -> Result<ARef<'_, T>, BorrowError> {
         Ok(ARef::new_ptr(this))
     }
     fn as_ref_cell(_this: &Self) -> Option<&RefCell<T>> {
@@ -195,17 +634,329 @@ impl<T: ?Sized> AsARef<T> for T {
 }
 
 impl<T: ?Sized> AsARef<T> for RefCell<T> {
-    fn as_aref(this: &Self) -> ARef<T> {
-        ARef::new_ref(this.borrow())
+    #[track_caller]
+    fn as_aref(this: &Self) -> ARef<'_, T> {
+        match Self::try_as_aref(this) {
+            Ok(x) => x,
+            Err(e) => panic!("{}{}", e, describe_borrows(this)),
+        }
     }
-    fn try_as_aref(this: &Self) -> Result<ARef<T>, BorrowError> {
-        Ok(ARef::new_ref(this.try_borrow()?))
+    #[track_caller]
+    fn try_as_aref(this: &Self) -> Result<ARef<'_, T>, BorrowError> {
+        Ok(ARef(
+            ARefImpl::new_ref(this.try_borrow()?),
+            BorrowTrack::new(this, false),
+        ))
     }
     fn as_ref_cell(this: &Self) -> Option<&RefCell<T>> {
         Some(this)
     }
 }
 
+/// Obtain an [`ARefMut`] from either a normal mutable pointer or a shared reference to
+/// a [`RefCell`]. Holding `&mut RefCell<T>` you can use [`RefCell::get_mut`] instead.
+///
+/// ```
+/// use std::cell::RefCell;
+///
+/// use gazebo::cell::AsARefMut;
+///
+/// fn push(x: &mut impl AsARefMut<String>) {
+///     AsARefMut::as_aref_mut(x).push('!');
+/// }
+///
+/// let cell = RefCell::new("hello".to_owned());
+/// push(&mut &cell);
+/// let mut s = "world".to_owned();
+/// push(&mut s);
+/// assert_eq!(
+///     (cell.into_inner(), s),
+///     ("hello!".to_owned(), "world!".to_owned())
+/// );
+/// ```
+pub trait AsARefMut<T: ?Sized> {
+    /// Get an [`ARefMut`] pointing at this type.
+    fn as_aref_mut(this: &mut Self) -> ARefMut<'_, T>;
+    /// Try and get an [`ARefMut`] pointing at this type. Returns an [`Err`] if
+    /// `Self` is a [`RefCell`] reference and the cell is already borrowed.
+    fn try_as_aref_mut(this: &mut Self) -> Result<ARefMut<'_, T>, BorrowMutError>;
+}
+
+impl<T: ?Sized> AsARefMut<T> for T {
+    fn as_aref_mut(this: &mut Self) -> ARefMut<'_, T> {
+        ARefMut::new_ptr(this)
+    }
+    fn try_as_aref_mut(this: &mut Self) -> Result<ARefMut<'_, T>, BorrowMutError> {
+        Ok(ARefMut::new_ptr(this))
+    }
+}
+
+impl<T: ?Sized> AsARefMut<T> for &RefCell<T> {
+    #[track_caller]
+    fn as_aref_mut(this: &mut Self) -> ARefMut<'_, T> {
+        let cell: &RefCell<T> = this;
+        match cell.try_borrow_mut() {
+            Ok(x) => ARefMut(ARefMutImpl::Ref(x), BorrowTrack::new(cell, true)),
+            Err(e) => panic!("{}{}", e, describe_borrows(cell)),
+        }
+    }
+    #[track_caller]
+    fn try_as_aref_mut(this: &mut Self) -> Result<ARefMut<'_, T>, BorrowMutError> {
+        let cell: &RefCell<T> = this;
+        Ok(ARefMut(
+            ARefMutImpl::Ref(cell.try_borrow_mut()?),
+            BorrowTrack::new(cell, true),
+        ))
+    }
+}
+
+/// A projection into the contents of an `Rc<RefCell<T>>` which owns both the [`Rc`]
+/// and a shared borrow of the [`RefCell`]. Like [`Ref`], but without a lifetime, so it
+/// can be returned from a function which only has the [`Rc`].
+///
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// use gazebo::cell::RcRef;
+///
+/// fn name(x: &Rc<RefCell<(String, u32)>>) -> RcRef<(String, u32), str> {
+///     RcRef::map(RcRef::new(x.clone()), |x| x.0.as_str())
+/// }
+///
+/// let x = Rc::new(RefCell::new(("gazebo".to_owned(), 1)));
+/// let n = name(&x);
+/// assert_eq!(&*n, "gazebo");
+/// assert!(x.try_borrow_mut().is_err());
+/// drop(n);
+/// assert!(x.try_borrow_mut().is_ok());
+/// ```
+pub struct RcRef<T: ?Sized, U: ?Sized = T> {
+    value: NonNull<U>,
+    // Fields are dropped in order, so the borrow is released before the `Rc`.
+    // The borrow is really only valid for as long as `rc`.
+    borrow: Ref<'static, ()>,
+    track: BorrowTrack,
+    rc: Rc<RefCell<T>>,
+}
+
+/// Like [`RcRef`], but holding a mutable borrow of the [`RefCell`].
+pub struct RcRefMut<T: ?Sized, U: ?Sized = T> {
+    value: NonNull<U>,
+    // Fields are dropped in order, so the borrow is released before the `Rc`.
+    // The borrow is really only valid for as long as `rc`.
+    borrow: RefMut<'static, ()>,
+    track: BorrowTrack,
+    rc: Rc<RefCell<T>>,
+    // We hand out `&mut U`, so must be invariant in `U`.
+    _marker: PhantomData<*mut U>,
+}
+
+impl<T: ?Sized> RcRef<T> {
+    /// Borrow the contents of an `Rc<RefCell<T>>`.
+    /// Panics if the value is currently mutably borrowed.
+    #[track_caller]
+    pub fn new(rc: Rc<RefCell<T>>) -> Self {
+        if let Err(e) = rc.try_borrow() {
+            panic!("{}{}", e, describe_borrows(&rc));
+        }
+        Self::try_new(rc).unwrap()
+    }
+
+    /// Borrow the contents of an `Rc<RefCell<T>>`, failing if the value is currently
+    /// mutably borrowed.
+    #[track_caller]
+    pub fn try_new(rc: Rc<RefCell<T>>) -> Result<Self, BorrowError> {
+        let mut value = None;
+        let borrow = Ref::map(rc.try_borrow()?, |x| {
+            value = Some(NonNull::from(x));
+            &()
+        });
+        // SAFETY: We keep `rc` alive for longer than `borrow`.
+        let borrow = unsafe { mem::transmute::<Ref<'_, ()>, Ref<'static, ()>>(borrow) };
+        Ok(RcRef {
+            value: value.unwrap(),
+            borrow,
+            track: BorrowTrack::new(&rc, false),
+            rc,
+        })
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> RcRef<T, U> {
+    /// The [`Rc`] this value is borrowed from. Not a self method since that interferes with
+    /// the [`Deref`].
+    pub fn rc(orig: &Self) -> &Rc<RefCell<T>> {
+        &orig.rc
+    }
+
+    /// See [`Ref.map`](Ref::map). Not a self method since that interferes with the [`Deref`].
+    pub fn map<V: ?Sized, F>(orig: RcRef<T, U>, f: F) -> RcRef<T, V>
+    where
+        F: FnOnce(&U) -> &V,
+    {
+        RcRef {
+            value: NonNull::from(f(&*orig)),
+            borrow: orig.borrow,
+            rc: orig.rc,
+            track: orig.track,
+        }
+    }
+
+    /// See [`Ref.filter_map`](Ref::filter_map). Not a self method since that interferes with the
+    /// [`Deref`].
+    pub fn filter_map<V: ?Sized, F>(orig: RcRef<T, U>, f: F) -> Result<RcRef<T, V>, Self>
+    where
+        F: FnOnce(&U) -> Option<&V>,
+    {
+        match f(&*orig).map(NonNull::from) {
+            Some(value) => Ok(RcRef {
+                value,
+                borrow: orig.borrow,
+                rc: orig.rc,
+                track: orig.track,
+            }),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Deref for RcRef<T, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        // SAFETY: The value is kept alive by `rc` and immutable while we hold `borrow`.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Clone for RcRef<T, U> {
+    fn clone(&self) -> Self {
+        RcRef {
+            value: self.value,
+            borrow: Ref::clone(&self.borrow),
+            rc: self.rc.dupe(),
+            track: self.track.duplicate(),
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Dupe for RcRef<T, U> {}
+
+impl<T: ?Sized, U: fmt::Debug + ?Sized> fmt::Debug for RcRef<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        RcRef::deref(self).fmt(f)
+    }
+}
+
+impl<T: ?Sized, U: Display + ?Sized> Display for RcRef<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        RcRef::deref(self).fmt(f)
+    }
+}
+
+impl<T: ?Sized> RcRefMut<T> {
+    /// Mutably borrow the contents of an `Rc<RefCell<T>>`.
+    /// Panics if the value is currently borrowed.
+    #[track_caller]
+    pub fn new(rc: Rc<RefCell<T>>) -> Self {
+        if let Err(e) = rc.try_borrow_mut() {
+            panic!("{}{}", e, describe_borrows(&rc));
+        }
+        Self::try_new(rc).unwrap()
+    }
+
+    /// Mutably borrow the contents of an `Rc<RefCell<T>>`, failing if the value is
+    /// currently borrowed.
+    #[track_caller]
+    pub fn try_new(rc: Rc<RefCell<T>>) -> Result<Self, BorrowMutError> {
+        let mut value = None;
+        let borrow = RefMut::map(rc.try_borrow_mut()?, |x| {
+            value = Some(NonNull::from(x));
+            // SAFETY: A dangling pointer is a valid reference to a zero-sized type.
+            unsafe { NonNull::<()>::dangling().as_mut() }
+        });
+        // SAFETY: We keep `rc` alive for longer than `borrow`.
+        let borrow = unsafe { mem::transmute::<RefMut<'_, ()>, RefMut<'static, ()>>(borrow) };
+        Ok(RcRefMut {
+            value: value.unwrap(),
+            borrow,
+            track: BorrowTrack::new(&rc, true),
+            rc,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> RcRefMut<T, U> {
+    /// The [`Rc`] this value is borrowed from. Not a self method since that interferes with
+    /// the [`Deref`].
+    pub fn rc(orig: &Self) -> &Rc<RefCell<T>> {
+        &orig.rc
+    }
+
+    /// See [`RefMut.map`](RefMut::map). Not a self method since that interferes with the
+    /// [`Deref`].
+    pub fn map<V: ?Sized, F>(mut orig: RcRefMut<T, U>, f: F) -> RcRefMut<T, V>
+    where
+        F: FnOnce(&mut U) -> &mut V,
+    {
+        RcRefMut {
+            value: NonNull::from(f(&mut *orig)),
+            borrow: orig.borrow,
+            rc: orig.rc,
+            track: orig.track,
+            _marker: PhantomData,
+        }
+    }
+
+    /// See [`RefMut.filter_map`](RefMut::filter_map). Not a self method since that interferes
+    /// with the [`Deref`].
+    pub fn filter_map<V: ?Sized, F>(mut orig: RcRefMut<T, U>, f: F) -> Result<RcRefMut<T, V>, Self>
+    where
+        F: FnOnce(&mut U) -> Option<&mut V>,
+    {
+        match f(&mut *orig).map(NonNull::from) {
+            Some(value) => Ok(RcRefMut {
+                value,
+                borrow: orig.borrow,
+                rc: orig.rc,
+                track: orig.track,
+                _marker: PhantomData,
+            }),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Deref for RcRefMut<T, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        // SAFETY: The value is kept alive by `rc` and we hold an exclusive `borrow`.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> DerefMut for RcRefMut<T, U> {
+    fn deref_mut(&mut self) -> &mut U {
+        // SAFETY: The value is kept alive by `rc` and we hold an exclusive `borrow`.
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized, U: fmt::Debug + ?Sized> fmt::Debug for RcRefMut<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        RcRefMut::deref(self).fmt(f)
+    }
+}
+
+impl<T: ?Sized, U: Display + ?Sized> Display for RcRefMut<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        RcRefMut::deref(self).fmt(f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::cell::RefCell;
@@ -285,9 +1036,72 @@ mod tests {
         let _ignore: Ref<String> = unsafe { mem::transmute((pointer, cell)) };
     }
 
+    #[test]
+    // Tracking borrows stores extra information in every `ARef`
+    #[cfg(not(feature = "track_borrows"))]
+    fn test_aref_size() {
+        let word = mem::size_of::<usize>();
+        assert_eq!(mem::size_of::<ARef<u32>>(), 2 * word);
+        assert_eq!(mem::size_of::<Option<ARef<u32>>>(), 2 * word);
+        assert_eq!(mem::size_of::<ARef<u32>>(), mem::size_of::<(&u32, usize)>());
+        assert_eq!(mem::size_of::<ARef<str>>(), mem::size_of::<(&str, usize)>());
+    }
+
+    #[test]
+    fn test_aref_borrow_count() {
+        let c = RefCell::new((1, 2));
+        let r = ARef::new_ref(c.borrow());
+        let r2 = ARef::clone(&r);
+        let (a, b) = ARef::map_split(r, |x| (&x.0, &x.1));
+        let b = ARef::filter_map(b, |_| None::<&()>).unwrap_err();
+        assert_eq!((*a, *b), (1, 2));
+        // Other shared borrows still work alongside
+        assert_eq!(*c.borrow(), (1, 2));
+        for x in [a, b] {
+            assert!(c.try_borrow_mut().is_err());
+            mem::drop(x);
+        }
+        assert!(c.try_borrow_mut().is_err());
+        mem::drop(r2);
+        assert!(c.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    fn test_aref_layout() {
+        // Both representations must point at the original value, not a copy of it
+        let x = ("test".to_owned(), 1u8);
+        let p = ARef::new_ptr(&x);
+        assert!(std::ptr::eq(&*p, &x));
+        let p = ARef::map(p, |x| &x.1);
+        assert!(std::ptr::eq(&*p, &x.1));
+        mem::drop(p);
+
+        let c = RefCell::new(x);
+        let r = ARef::new_ref(c.borrow());
+        assert!(std::ptr::eq(&*r, c.as_ptr()));
+        let (a, b) = ARef::map_split(r, |x| (x.0.as_str(), &x.1));
+        assert_eq!((&*a, *b), ("test", 1));
+        mem::drop(a);
+        assert!(c.try_borrow_mut().is_err());
+        mem::drop(b);
+        assert!(c.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    fn test_many_pointers() {
+        let xs: Vec<u32> = (0..1000).collect();
+        let ps: Vec<ARef<u32>> = xs.iter().map(ARef::new_ptr).collect();
+        let ps2: Vec<ARef<u32>> = ps.iter().map(ARef::clone).collect();
+        assert_eq!(ps, ps2);
+        mem::drop(ps);
+        let total: u32 = ps2.iter().map(|x| **x).sum();
+        assert_eq!(total, 499500);
+        std::thread::spawn(|| *ARef::new_ptr(&7)).join().unwrap();
+    }
+
     #[test]
     fn test_as_aref() {
-        fn get_str(x: &impl AsARef<String>) -> ARef<str> {
+        fn get_str(x: &impl AsARef<String>) -> ARef<'_, str> {
             ARef::map(AsARef::as_aref(x), |x| x.as_str())
         }
 
@@ -296,4 +1110,239 @@ mod tests {
         assert_eq!(&*get_str(&a), "hello");
         assert_eq!(&*get_str(&b), "world");
     }
+
+    #[test]
+    fn test_mut_borrow_guards() {
+        let c = RefCell::new(5);
+        let mut r = ARefMut::new_ref(c.borrow_mut());
+        assert!(c.try_borrow().is_err());
+        *r += 1;
+        mem::drop(r);
+        assert_eq!(*c.borrow(), 6);
+    }
+
+    #[test]
+    fn test_mut_map() {
+        let mut s = "test".to_owned();
+        let mut p = ARefMut::map(ARefMut::new_ptr(&mut s), |x| x.as_mut_str());
+        p.make_ascii_uppercase();
+        mem::drop(p);
+        assert_eq!(s, "TEST");
+
+        let c = RefCell::new((1, 'a'));
+        let mut p = ARefMut::map(ARefMut::new_ref(c.borrow_mut()), |x| &mut x.0);
+        *p = 2;
+        mem::drop(p);
+        assert_eq!(*c.borrow(), (2, 'a'));
+    }
+
+    #[test]
+    fn test_mut_map_split() {
+        let mut xs = [1, 2, 3, 4];
+        let (mut begin, mut end) =
+            ARefMut::map_split(ARefMut::new_ptr(&mut xs[..]), |x| x.split_at_mut(2));
+        begin[0] = 10;
+        end[0] = 30;
+        mem::drop((begin, end));
+        assert_eq!(xs, [10, 2, 30, 4]);
+
+        let c = RefCell::new([1, 2, 3, 4]);
+        let (begin, end) =
+            ARefMut::map_split(ARefMut::new_ref(c.borrow_mut()), |x| x.split_at_mut(2));
+        mem::drop(begin);
+        assert!(c.try_borrow().is_err());
+        mem::drop(end);
+        assert!(c.try_borrow().is_ok());
+    }
+
+    #[test]
+    fn test_mut_filter_map() {
+        let mut xs = vec![1, 2, 3];
+        let p = ARefMut::new_ptr(&mut xs);
+        let p = ARefMut::filter_map(p, |x| x.get_mut(5)).unwrap_err();
+        let mut p = ARefMut::filter_map(p, |x| x.get_mut(1)).unwrap();
+        *p = 20;
+        mem::drop(p);
+        assert_eq!(xs, [1, 20, 3]);
+
+        let c = RefCell::new(vec![1, 2, 3]);
+        let p = ARefMut::new_ref(c.borrow_mut());
+        let p = ARefMut::filter_map(p, |x| x.get_mut(5)).unwrap_err();
+        let p = ARefMut::filter_map(p, |x| x.get_mut(1)).unwrap();
+        assert_eq!(*p, 2);
+        mem::drop(p);
+        assert!(c.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    fn test_as_aref_mut() {
+        fn push_str(x: &mut impl AsARefMut<String>) {
+            AsARefMut::as_aref_mut(x).push('!');
+        }
+
+        let a = RefCell::new("hello".to_owned());
+        let mut b = "world".to_owned();
+        push_str(&mut &a);
+        push_str(&mut b);
+        assert_eq!(a.into_inner(), "hello!");
+        assert_eq!(b, "world!");
+
+        let c = RefCell::new(1);
+        let r = c.borrow();
+        assert!(<&RefCell<i32> as AsARefMut<i32>>::try_as_aref_mut(&mut &c).is_err());
+        mem::drop(r);
+        let mut shared = &c;
+        let mut m = <&RefCell<i32> as AsARefMut<i32>>::try_as_aref_mut(&mut shared).unwrap();
+        *m += 1;
+        assert!(c.try_borrow().is_err());
+        mem::drop(m);
+        assert_eq!(*c.borrow(), 2);
+    }
+
+    #[test]
+    fn test_rc_ref() {
+        let rc = Rc::new(RefCell::new(vec![1, 2, 3]));
+        let r = RcRef::new(rc.dupe());
+        let second = RcRef::map(r.dupe(), |x| &x[1]);
+        assert_eq!(*second, 2);
+        assert_eq!(r.len(), 3);
+        assert!(rc.try_borrow_mut().is_err());
+        mem::drop(r);
+        assert!(rc.try_borrow_mut().is_err());
+        mem::drop(second);
+        assert!(rc.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    fn test_rc_ref_outlives_rc() {
+        fn first(x: Rc<RefCell<Vec<String>>>) -> RcRef<Vec<String>, str> {
+            RcRef::map(RcRef::new(x), |x| x[0].as_str())
+        }
+
+        let rc = Rc::new(RefCell::new(vec!["hello".to_owned()]));
+        let weak = Rc::downgrade(&rc);
+        let r = first(rc);
+        assert_eq!(&*r, "hello");
+        assert!(weak.upgrade().is_some());
+        mem::drop(r);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_rc_ref_filter_map() {
+        let rc = Rc::new(RefCell::new(vec![1, 2, 3]));
+        let r = RcRef::filter_map(RcRef::new(rc.dupe()), |x| x.get(5)).unwrap_err();
+        let r = RcRef::filter_map(r, |x| x.get(2)).unwrap();
+        assert_eq!(*r, 3);
+        assert!(Rc::ptr_eq(RcRef::rc(&r), &rc));
+        assert!(RcRefMut::try_new(rc.dupe()).is_err());
+        mem::drop(r);
+        assert!(RcRefMut::try_new(rc).is_ok());
+    }
+
+    #[test]
+    fn test_rc_ref_mut() {
+        let rc = Rc::new(RefCell::new(("a".to_owned(), 1)));
+        let mut r = RcRefMut::map(RcRefMut::new(rc.dupe()), |x| &mut x.0);
+        r.push('b');
+        assert!(rc.try_borrow().is_err());
+        assert!(RcRef::try_new(rc.dupe()).is_err());
+        let r = RcRefMut::filter_map(r, |_| None::<&mut u8>).unwrap_err();
+        assert_eq!(r.to_string(), "ab");
+        mem::drop(r);
+        assert_eq!(*rc.borrow(), ("ab".to_owned(), 1));
+    }
+
+    #[cfg(feature = "track_borrows")]
+    #[test]
+    fn test_track_borrows() {
+        let c = RefCell::new(vec![1, 2, 3]);
+        let line = line!() + 1;
+        let r: ARef<Vec<i32>> = AsARef::as_aref(&c);
+        let (r1, r2) = ARef::map_split(r, |x| x.split_at(1));
+        let sites = borrow_sites(&c);
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[0].location.line(), line);
+        assert!(!sites[0].mutable);
+        assert!(c.try_borrow_mut().is_err());
+        mem::drop(r1);
+        assert_eq!(borrow_sites(&c).len(), 1);
+        mem::drop(r2);
+        assert!(borrow_sites(&c).is_empty());
+        // Borrows taken directly from the `RefCell` are not tracked
+        let _r = c.borrow();
+        assert!(borrow_sites(&c).is_empty());
+    }
+
+    #[cfg(feature = "track_borrows")]
+    #[test]
+    fn test_track_borrows_panic() {
+        let rc = Rc::new(RefCell::new(1));
+        let line = line!() + 1;
+        let r = RcRefMut::new(rc.dupe());
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _: ARef<i32> = AsARef::as_aref(&*rc);
+        }))
+        .unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("already mutably borrowed"), "{}", msg);
+        assert!(
+            msg.contains(&format!("mutable borrow at {}:{}:", file!(), line)),
+            "{}",
+            msg
+        );
+        assert!(<RefCell<i32> as AsARef<i32>>::try_as_aref(&rc).is_err());
+        assert_eq!(borrow_sites(&*rc).len(), 1);
+        mem::drop(r);
+        assert!(borrow_sites(&*rc).is_empty());
+        assert_eq!(*<RefCell<i32> as AsARef<i32>>::as_aref(&rc), 1);
+    }
+
+    #[cfg(feature = "track_borrows")]
+    #[test]
+    fn test_track_borrows_mut() {
+        let c = RefCell::new((1, 2));
+        let mut shared = &c;
+        let line = line!() + 1;
+        let r: ARefMut<(i32, i32)> = AsARefMut::as_aref_mut(&mut shared);
+        let (a, b) = ARefMut::map_split(r, |x| (&mut x.0, &mut x.1));
+        assert_eq!(
+            borrow_sites(&c)
+                .iter()
+                .map(|x| (x.location.line(), x.mutable))
+                .collect::<Vec<_>>(),
+            vec![(line, true), (line, true)]
+        );
+        mem::drop(a);
+
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _: ARef<(i32, i32)> = AsARef::as_aref(&c);
+        }))
+        .unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("already mutably borrowed"), "{}", msg);
+        assert!(
+            msg.contains(&format!("mutable borrow at {}:{}:", file!(), line)),
+            "{}",
+            msg
+        );
+
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut shared = &c;
+            let _: ARefMut<(i32, i32)> = AsARefMut::as_aref_mut(&mut shared);
+        }))
+        .unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(
+            msg.contains(&format!("mutable borrow at {}:{}:", file!(), line)),
+            "{}",
+            msg
+        );
+        mem::drop(b);
+        assert!(borrow_sites(&c).is_empty());
+
+        // Wrapping a guard taken directly isn't tracked
+        let _r = ARefMut::new_ref(c.borrow_mut());
+        assert!(borrow_sites(&c).is_empty());
+    }
 }
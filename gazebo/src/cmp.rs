@@ -9,8 +9,16 @@
 
 //! Traits to help implementing dynamic comparisons.
 
-/// Performs a chain of comparison operation expressions yielding `std::cmp::Ordering`, supporting
-/// early exit upon hitting the first expressions that doesn't yield `std::cmp::Ordering::Equal`
+use core::cmp::Ordering;
+use core::fmt;
+use core::fmt::Display;
+use core::hash::Hash;
+use core::hash::Hasher;
+
+use dupe::Dupe;
+
+/// Performs a chain of comparison operation expressions yielding `core::cmp::Ordering`, supporting
+/// early exit upon hitting the first expressions that doesn't yield `core::cmp::Ordering::Equal`
 /// and returning the result of that. This is useful for easily writing a sequence of expressions
 /// necessary to yield a comparison result.
 /// The macro is expanded inplace, so any expressions dealing with `Result` types are allowed
@@ -40,8 +48,8 @@ macro_rules! cmp_chain {
     };
     ($e:expr, $($x:expr),+ $(,)?) => {
         match $e {
-            std::cmp::Ordering::Equal => {
-                cmp_chain!($($x),+)
+            ::core::cmp::Ordering::Equal => {
+                $crate::cmp_chain!($($x),+)
             },
             c => {
                 c
@@ -78,14 +86,501 @@ macro_rules! eq_chain {
     };
     ($e:expr, $($x:expr),+ $(,)?) => {
         if $e {
-            eq_chain!($($x),+)
+            $crate::eq_chain!($($x),+)
         } else {
             false
         }
     };
 }
 
+/// Like [`cmp_chain!`], but for expressions yielding `Option<core::cmp::Ordering>`, as
+/// returned by [`PartialOrd::partial_cmp`]. Exits early upon hitting the first expression
+/// that doesn't yield `Some(core::cmp::Ordering::Equal)`, returning the result of that.
+///
+/// ```
+/// use std::cmp::Ordering;
+///
+/// use gazebo::partial_cmp_chain;
+///
+/// assert_eq!(
+///     partial_cmp_chain! {
+///         1.0.partial_cmp(&1.0),
+///         f64::NAN.partial_cmp(&2.0),
+///         panic!("won't reach this"),
+///     },
+///     None,
+/// );
+/// assert_eq!(
+///     partial_cmp_chain! {
+///         1.0.partial_cmp(&1.0),
+///         2.0.partial_cmp(&1.0),
+///     },
+///     Some(Ordering::Greater),
+/// );
+/// ```
+#[macro_export]
+macro_rules! partial_cmp_chain {
+    ($e:expr) => {
+        $e
+    };
+    ($e:expr, $($x:expr),+ $(,)?) => {
+        match $e {
+            ::core::option::Option::Some(::core::cmp::Ordering::Equal) => {
+                $crate::partial_cmp_chain!($($x),+)
+            },
+            c => {
+                c
+            }
+        }
+    };
+}
+
+/// Like [`cmp_chain!`], but for expressions yielding `Result<core::cmp::Ordering, E>`, such
+/// as those passed to `try_cmp_by`. Exits early upon hitting the first expression that
+/// doesn't yield `Ok(core::cmp::Ordering::Equal)`, returning the result of that.
+///
+/// ```
+/// use std::cmp::Ordering;
+///
+/// use gazebo::try_cmp_chain;
+///
+/// let cmp_non_zero = |x: i32, y: i32| {
+///     if x == 0 || y == 0 {
+///         Err("zero")
+///     } else {
+///         Ok(x.cmp(&y))
+///     }
+/// };
+///
+/// assert_eq!(
+///     try_cmp_chain! {
+///         cmp_non_zero(1, 1),
+///         cmp_non_zero(2, 3),
+///         panic!("won't reach this"),
+///     },
+///     Ok(Ordering::Less),
+/// );
+/// assert_eq!(
+///     try_cmp_chain! {
+///         cmp_non_zero(1, 1),
+///         cmp_non_zero(0, 3),
+///         panic!("won't reach this"),
+///     },
+///     Err("zero"),
+/// );
+/// ```
+#[macro_export]
+macro_rules! try_cmp_chain {
+    ($e:expr) => {
+        $e
+    };
+    ($e:expr, $($x:expr),+ $(,)?) => {
+        match $e {
+            ::core::result::Result::Ok(::core::cmp::Ordering::Equal) => {
+                $crate::try_cmp_chain!($($x),+)
+            },
+            c => {
+                c
+            }
+        }
+    };
+}
+
+/// Hashes a sequence of expressions into a `Hasher`, given as the first argument. This is
+/// the companion of [`eq_chain!`], so that `Hash` and `PartialEq` implementations can be
+/// written next to each other and kept consistent.
+///
+/// ```
+/// use std::hash::Hash;
+/// use std::hash::Hasher;
+///
+/// use gazebo::eq_chain;
+/// use gazebo::hash_chain;
+///
+/// struct Point {
+///     x: i32,
+///     y: i32,
+///     cached_len: f64,
+/// }
+///
+/// impl PartialEq for Point {
+///     fn eq(&self, other: &Self) -> bool {
+///         eq_chain!(self.x == other.x, self.y == other.y)
+///     }
+/// }
+///
+/// impl Hash for Point {
+///     fn hash<H: Hasher>(&self, state: &mut H) {
+///         hash_chain!(state, self.x, self.y)
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! hash_chain {
+    ($state:expr, $($x:expr),+ $(,)?) => {{
+        let state = $state;
+        $(::core::hash::Hash::hash(&$x, state);)+
+    }};
+}
+
+/// Derive `Hash` over only the keys listed in the `#[ord_by(...)]` attribute, consistent
+/// with [`OrdBy`](derive@OrdBy). Directions are ignored, so `reverse(key)` hashes `key`.
+pub use gazebo_derive::HashBy;
+/// Derive `PartialEq`, `Eq`, `PartialOrd` and `Ord`, comparing only the keys listed in the
+/// `#[ord_by(...)]` attribute, in order. Each key is one of:
+///
+/// * A field name (or index, for tuple structs).
+/// * `key = expr`, where `expr` can refer to the fields by name (or as `_0`, `_1` etc. for
+///   tuple structs), bound by reference.
+/// * `reverse(key)`, which compares `key` in the opposite direction.
+///
+/// The comparisons are generated with [`cmp_chain!`] and [`eq_chain!`], so only evaluate as
+/// many keys as needed. Use `#[derive(HashBy)]` alongside to get a consistent `Hash`.
+///
+/// ```
+/// use gazebo::cmp::HashBy;
+/// use gazebo::cmp::OrdBy;
+///
+/// #[derive(OrdBy, HashBy)]
+/// #[ord_by(priority, reverse(created_at), key = name.len())]
+/// struct Task {
+///     priority: u32,
+///     created_at: u64,
+///     name: String,
+///     description: String,
+/// }
+///
+/// let task = |priority, created_at, name: &str, description: &str| Task {
+///     priority,
+///     created_at,
+///     name: name.to_owned(),
+///     description: description.to_owned(),
+/// };
+/// assert!(task(1, 5, "a", "") < task(2, 5, "a", ""));
+/// assert!(task(1, 5, "a", "") < task(1, 4, "a", ""));
+/// assert!(task(1, 5, "a", "") < task(1, 5, "bb", ""));
+/// assert!(task(1, 5, "a", "x") == task(1, 5, "b", "y"));
+/// ```
+pub use gazebo_derive::OrdBy;
+
+/// Compare strings in natural (human) order, where runs of ASCII digits are compared by
+/// their numeric value, so `"file2" < "file10"`. Strings which only differ in leading
+/// zeros are ordered by [`str::cmp`], so the result is only `Equal` for equal strings.
+///
+/// ```
+/// use std::cmp::Ordering;
+///
+/// use gazebo::cmp::natural_cmp;
+///
+/// assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+/// assert_eq!(natural_cmp("v1.10.0", "v1.9.3"), Ordering::Greater);
+/// assert_eq!(natural_cmp("B", "a"), Ordering::Less);
+///
+/// let mut xs = vec!["x10", "x9", "x1", "x010"];
+/// xs.sort_by(|a, b| natural_cmp(a, b));
+/// assert_eq!(xs, ["x1", "x9", "x010", "x10"]);
+/// ```
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    cmp_chain!(natural_cmp_by(a, b, |x, y| x.cmp(&y)), a.cmp(b))
+}
+
+/// Like [`natural_cmp`], but comparing characters case-insensitively, by their lowercase
+/// forms. Strings which only differ in case are ordered by [`natural_cmp`], so the result
+/// is only `Equal` for equal strings.
+///
+/// ```
+/// use std::cmp::Ordering;
+///
+/// use gazebo::cmp::natural_cmp_case_insensitive;
+///
+/// assert_eq!(natural_cmp_case_insensitive("B2", "a10"), Ordering::Greater);
+/// assert_eq!(
+///     natural_cmp_case_insensitive("item2", "Item10"),
+///     Ordering::Less
+/// );
+/// ```
+pub fn natural_cmp_case_insensitive(a: &str, b: &str) -> Ordering {
+    cmp_chain!(
+        natural_cmp_by(a, b, |x, y| x.to_lowercase().cmp(y.to_lowercase())),
+        natural_cmp(a, b)
+    )
+}
+
+fn natural_cmp_by(mut a: &str, mut b: &str, cmp_char: impl Fn(char, char) -> Ordering) -> Ordering {
+    fn split_digits(x: &str) -> (&str, &str) {
+        x.split_at(x.find(|c: char| !c.is_ascii_digit()).unwrap_or(x.len()))
+    }
+
+    loop {
+        let (x, y) = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => (x, y),
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (xs, a_rest) = split_digits(a);
+            let (ys, b_rest) = split_digits(b);
+            let xs = xs.trim_start_matches('0');
+            let ys = ys.trim_start_matches('0');
+            // Without leading zeros, a longer run of digits is a bigger number.
+            match xs.len().cmp(&ys.len()).then_with(|| xs.cmp(ys)) {
+                Ordering::Equal => {}
+                c => return c,
+            }
+            a = a_rest;
+            b = b_rest;
+        } else {
+            match cmp_char(x, y) {
+                Ordering::Equal => {}
+                c => return c,
+            }
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+        }
+    }
+}
+
+/// A comparator comparing by the key returned by `f`. Like [`slice::sort_by_key`], but
+/// produces a comparator which can be combined with [`then_by`], [`reversed`] etc.
+///
+/// ```
+/// use gazebo::cmp::by_key;
+/// use gazebo::cmp::reversed;
+/// use gazebo::cmp::then_by;
+///
+/// let mut xs = vec![("b", 1), ("a", 2), ("c", 1)];
+/// xs.sort_by(then_by(
+///     by_key(|x: &(&str, i32)| x.1),
+///     reversed(by_key(|x: &(&str, i32)| x.0)),
+/// ));
+/// assert_eq!(xs, [("c", 1), ("b", 1), ("a", 2)]);
+/// ```
+pub fn by_key<T: ?Sized, K: Ord>(f: impl Fn(&T) -> K) -> impl Fn(&T, &T) -> Ordering {
+    move |x, y| f(x).cmp(&f(y))
+}
+
+/// A comparator using `first`, and if that returns `Equal`, then `second`.
+pub fn then_by<T: ?Sized>(
+    first: impl Fn(&T, &T) -> Ordering,
+    second: impl Fn(&T, &T) -> Ordering,
+) -> impl Fn(&T, &T) -> Ordering {
+    move |x, y| cmp_chain!(first(x, y), second(x, y))
+}
+
+/// A comparator which orders in the opposite direction to `cmp`.
+pub fn reversed<T: ?Sized>(cmp: impl Fn(&T, &T) -> Ordering) -> impl Fn(&T, &T) -> Ordering {
+    move |x, y| cmp(y, x)
+}
+
+/// A comparator on [`Option`] which puts `None` before any `Some`, and otherwise uses `cmp`.
+///
+/// ```
+/// use gazebo::cmp::nulls_first;
+/// use gazebo::cmp::nulls_last;
+/// use gazebo::cmp::reversed;
+///
+/// let mut xs = vec![Some(1), None, Some(3)];
+/// xs.sort_by(nulls_first(reversed(i32::cmp)));
+/// assert_eq!(xs, [None, Some(3), Some(1)]);
+/// xs.sort_by(nulls_last(i32::cmp));
+/// assert_eq!(xs, [Some(1), Some(3), None]);
+/// ```
+pub fn nulls_first<T>(
+    cmp: impl Fn(&T, &T) -> Ordering,
+) -> impl Fn(&Option<T>, &Option<T>) -> Ordering {
+    move |x, y| match (x, y) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(x), Some(y)) => cmp(x, y),
+    }
+}
+
+/// A comparator on [`Option`] which puts `None` after any `Some`, and otherwise uses `cmp`.
+pub fn nulls_last<T>(
+    cmp: impl Fn(&T, &T) -> Ordering,
+) -> impl Fn(&Option<T>, &Option<T>) -> Ordering {
+    move |x, y| match (x, y) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(x), Some(y)) => cmp(x, y),
+    }
+}
+
+/// Turn a comparator into one returning `Ok`, for use with fallible comparisons such as
+/// [`IterExt::try_cmp_by`](crate::prelude::IterExt::try_cmp_by).
+///
+/// ```
+/// use std::cmp::Ordering;
+///
+/// use gazebo::cmp::by_key;
+/// use gazebo::cmp::infallible;
+/// use gazebo::prelude::*;
+///
+/// let xs = ["a", "bb"];
+/// let ys = ["cc", "d"];
+/// assert_eq!(
+///     xs.iter()
+///         .try_cmp_by(&ys, infallible::<_, ()>(by_key(|x: &&str| x.len()))),
+///     Ok(Ordering::Less),
+/// );
+/// ```
+pub fn infallible<T: ?Sized, E>(
+    cmp: impl Fn(&T, &T) -> Ordering,
+) -> impl Fn(&T, &T) -> Result<Ordering, E> {
+    move |x, y| Ok(cmp(x, y))
+}
+
+macro_rules! ord_float {
+    ($(#[$doc:meta])* $name:ident, $t:ty) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Dupe, Debug, Default)]
+        pub struct $name(pub $t);
+
+        impl PartialEq for $name {
+            #[inline]
+            fn eq(&self, other: &Self) -> bool {
+                self.0.to_bits() == other.0.to_bits()
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl PartialOrd for $name {
+            #[inline]
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            #[inline]
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.total_cmp(&other.0)
+            }
+        }
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.to_bits().hash(state)
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                Display::fmt(&self.0, f)
+            }
+        }
+
+        impl From<$t> for $name {
+            #[inline]
+            fn from(x: $t) -> Self {
+                $name(x)
+            }
+        }
+    };
+}
+
+ord_float!(
+    /// An [`f32`] ordered by the IEEE 754 total order, as [`f32::total_cmp`], so it can
+    /// be used as a key in sorted maps, [`Hashed`](crate::hash::Hashed) etc.
+    ///
+    /// Values compare and hash by their bits, so `-0.0 < 0.0`, and NaNs are only equal to
+    /// NaNs with the same bits. Positive NaNs are greater than infinity, and negative NaNs
+    /// less than negative infinity.
+    OrdF32,
+    f32
+);
+
+ord_float!(
+    /// An [`f64`] ordered by the IEEE 754 total order, as [`f64::total_cmp`], so it can
+    /// be used as a key in sorted maps, [`Hashed`](crate::hash::Hashed) etc.
+    ///
+    /// Values compare and hash by their bits, so `-0.0 < 0.0`, and NaNs are only equal to
+    /// NaNs with the same bits. Positive NaNs are greater than infinity, and negative NaNs
+    /// less than negative infinity.
+    ///
+    /// ```
+    /// use gazebo::cmp::OrdF64;
+    ///
+    /// let mut xs = vec![OrdF64(1.0), OrdF64(f64::NAN), OrdF64(-0.0), OrdF64(0.0)];
+    /// xs.sort();
+    /// assert_eq!(xs[..3], [OrdF64(-0.0), OrdF64(0.0), OrdF64(1.0)]);
+    /// assert!(xs[3].0.is_nan());
+    /// ```
+    OrdF64,
+    f64
+);
+
+/// An [`f64`] which is known not to be NaN, ordered by the normal float comparison.
+///
+/// Unlike [`OrdF64`], `-0.0` and `0.0` compare equal (as they do for [`f64`]), and hash the
+/// same, since zeros are normalised before hashing.
+///
+/// ```
+/// use gazebo::cmp::NotNanF64;
+///
+/// assert!(NotNanF64::new(f64::NAN).is_none());
+/// let x = NotNanF64::new(-0.0).unwrap();
+/// assert_eq!(x, NotNanF64::new(0.0).unwrap());
+/// assert!(x < NotNanF64::new(f64::INFINITY).unwrap());
+/// ```
+#[derive(Clone, Copy, Dupe, Debug, Default, PartialEq)]
+pub struct NotNanF64(f64);
+
+impl NotNanF64 {
+    /// Returns [`None`] if the value is NaN.
+    #[inline]
+    pub fn new(x: f64) -> Option<Self> {
+        if x.is_nan() {
+            None
+        } else {
+            Some(NotNanF64(x))
+        }
+    }
+
+    /// The underlying value, which is never NaN.
+    #[inline]
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+impl Eq for NotNanF64 {}
+
+impl PartialOrd for NotNanF64 {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NotNanF64 {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Can't fail, since neither value is NaN.
+        self.0.partial_cmp(&other.0).unwrap()
+    }
+}
+
+impl Hash for NotNanF64 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // `-0.0 == 0.0`, so they must hash the same.
+        let x = if self.0 == 0.0 { 0.0 } else { self.0 };
+        x.to_bits().hash(state)
+    }
+}
+
+impl Display for NotNanF64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use std::cmp::Ordering;
 
@@ -161,4 +656,195 @@ mod tests {
         assert_eq!(fake.cmp(&fake), Ordering::Equal);
         assert_eq!(fake.eq(&fake), true);
     }
+
+    #[test]
+    fn partial_try_cmp_chain() {
+        fn check(x: Option<Ordering>, y: Option<Ordering>) -> Option<Ordering> {
+            partial_cmp_chain!(x, y, unreachable!("should have stopped"))
+        }
+        assert_eq!(check(Some(Ordering::Less), None), Some(Ordering::Less));
+        assert_eq!(check(None, Some(Ordering::Less)), None);
+        assert_eq!(check(Some(Ordering::Equal), None), None);
+        assert_eq!(
+            partial_cmp_chain!(Some(Ordering::Equal), Some(Ordering::Equal)),
+            Some(Ordering::Equal)
+        );
+
+        fn try_check(x: Result<Ordering, ()>, y: Result<Ordering, ()>) -> Result<Ordering, ()> {
+            try_cmp_chain!(x, y, unreachable!("should have stopped"))
+        }
+        assert_eq!(
+            try_check(Ok(Ordering::Greater), Err(())),
+            Ok(Ordering::Greater)
+        );
+        assert_eq!(try_check(Err(()), Ok(Ordering::Less)), Err(()));
+        assert_eq!(try_check(Ok(Ordering::Equal), Err(())), Err(()));
+        assert_eq!(
+            try_cmp_chain!(Ok::<_, ()>(Ordering::Equal), Ok(Ordering::Less)),
+            Ok(Ordering::Less)
+        );
+    }
+
+    #[test]
+    fn hash_chain() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::Hash;
+        use std::hash::Hasher;
+
+        fn hash_chained(x: i32, y: &str) -> u64 {
+            let mut h = DefaultHasher::new();
+            hash_chain!(&mut h, x, y);
+            h.finish()
+        }
+        let mut h = DefaultHasher::new();
+        1.hash(&mut h);
+        "test".hash(&mut h);
+        assert_eq!(hash_chained(1, "test"), h.finish());
+        assert_ne!(hash_chained(1, "test"), hash_chained(2, "test"));
+    }
+
+    #[test]
+    fn derive_ord_by() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::Hash;
+        use std::hash::Hasher;
+
+        #[allow(unused_imports)]
+        // Not actually unused, this makes testing the derive macro work
+        use crate as gazebo;
+        use crate::cmp::HashBy;
+        use crate::cmp::OrdBy;
+
+        #[derive(OrdBy, HashBy)]
+        #[ord_by(reverse(0), key = _1.len())]
+        struct Entry(u32, String, #[allow(dead_code)] bool);
+
+        fn hash(x: &Entry) -> u64 {
+            let mut h = DefaultHasher::new();
+            x.hash(&mut h);
+            h.finish()
+        }
+
+        let a = Entry(1, "a".to_owned(), true);
+        let b = Entry(2, "a".to_owned(), true);
+        let c = Entry(1, "bb".to_owned(), true);
+        let d = Entry(1, "c".to_owned(), false);
+        assert_eq!(a.cmp(&b), Ordering::Greater);
+        assert_eq!(a.cmp(&c), Ordering::Less);
+        assert_eq!(a.partial_cmp(&d), Some(Ordering::Equal));
+        assert!(a == d);
+        assert!(a != c);
+        assert_eq!(hash(&a), hash(&d));
+        assert_ne!(hash(&a), hash(&c));
+
+        // Double reversal compares in the normal direction.
+        #[derive(OrdBy)]
+        #[ord_by(reverse(reverse(x)))]
+        struct Twice {
+            x: u32,
+        }
+        assert!(Twice { x: 1 } < Twice { x: 2 });
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        use super::*;
+
+        let mut xs = vec![
+            "file10.txt",
+            "file2.txt",
+            "File1.txt",
+            "file02.txt",
+            "file",
+            "file1.txt",
+            "99999999999999999999999",
+            "100000000000000000000000",
+            "a\u{e9}2",
+            "a\u{e9}10",
+        ];
+        xs.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            xs,
+            [
+                "99999999999999999999999",
+                "100000000000000000000000",
+                "File1.txt",
+                "a\u{e9}2",
+                "a\u{e9}10",
+                "file",
+                "file1.txt",
+                "file02.txt",
+                "file2.txt",
+                "file10.txt",
+            ]
+        );
+        xs.sort_by(|a, b| natural_cmp_case_insensitive(a, b));
+        assert_eq!(&xs[2..5], ["a\u{e9}2", "a\u{e9}10", "file"]);
+        assert_eq!(&xs[5..7], ["File1.txt", "file1.txt"]);
+        for x in &xs {
+            assert_eq!(natural_cmp(x, x), Ordering::Equal);
+            assert_eq!(natural_cmp_case_insensitive(x, x), Ordering::Equal);
+        }
+    }
+
+    #[test]
+    fn test_comparators() {
+        use super::*;
+
+        let cmp = then_by(
+            by_key(|x: &(i32, i32)| x.0),
+            reversed(by_key(|x: &(i32, i32)| x.1)),
+        );
+        assert_eq!(cmp(&(1, 2), &(2, 1)), Ordering::Less);
+        assert_eq!(cmp(&(1, 2), &(1, 1)), Ordering::Less);
+        assert_eq!(cmp(&(1, 2), &(1, 2)), Ordering::Equal);
+
+        let cmp = nulls_first(i32::cmp);
+        assert_eq!(cmp(&None, &Some(1)), Ordering::Less);
+        assert_eq!(cmp(&Some(2), &Some(1)), Ordering::Greater);
+        let cmp = nulls_last(i32::cmp);
+        assert_eq!(cmp(&None, &Some(1)), Ordering::Greater);
+        assert_eq!(cmp(&None, &None), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_ord_float() {
+        use std::collections::BTreeSet;
+
+        use cmp_any::OrdAny;
+
+        use super::*;
+        use crate::hash::Hashed;
+
+        assert_eq!(OrdF64(-0.0).cmp(&OrdF64(0.0)), Ordering::Less);
+        assert_ne!(OrdF32(-0.0), OrdF32(0.0));
+        assert_eq!(OrdF64(f64::NAN), OrdF64(f64::NAN));
+        assert!(OrdF64(f64::NAN) > OrdF64(f64::INFINITY));
+        assert!(OrdF64(-f64::NAN) < OrdF64(f64::NEG_INFINITY));
+        assert_eq!(OrdF32(1.5).to_string(), "1.5");
+
+        assert_eq!(Hashed::new(OrdF64(1.0)), Hashed::new(OrdF64(1.0)));
+        assert_ne!(Hashed::new(OrdF64(0.0)), Hashed::new(OrdF64(-0.0)));
+        let set: BTreeSet<_> = [OrdF64(2.0), OrdF64(1.0), OrdF64(2.0)]
+            .into_iter()
+            .collect();
+        assert_eq!(set.len(), 2);
+
+        let (x, y) = (OrdF64(1.0), OrdF64(2.0));
+        assert_eq!(OrdAny::new(&x).cmp(&OrdAny::new(&y)), Ordering::Less);
+    }
+
+    #[test]
+    fn test_not_nan() {
+        use super::*;
+        use crate::hash::Hashed;
+
+        assert_eq!(NotNanF64::new(f64::NAN), None);
+        let zero = NotNanF64::new(0.0).unwrap();
+        let neg_zero = NotNanF64::new(-0.0).unwrap();
+        assert_eq!(zero.cmp(&neg_zero), Ordering::Equal);
+        assert_eq!(Hashed::new(zero), Hashed::new(neg_zero));
+        assert!(NotNanF64::new(f64::NEG_INFINITY).unwrap() < neg_zero);
+        assert_eq!(zero.dupe().get(), 0.0);
+    }
 }
@@ -7,7 +7,8 @@
  * of this source tree.
  */
 
-use std::cmp::Ordering;
+use alloc::borrow::ToOwned;
+use core::cmp::Ordering;
 
 /// Extension traits on [`Iterator`](Iterator).
 pub trait IterExt {
@@ -7,8 +7,9 @@
  * of this source tree.
  */
 
+use core::cmp::Ordering;
 #[cfg(feature = "str_pattern_extensions")]
-use std::str::pattern::*;
+use core::str::pattern::*;
 
 /// Extension traits on [`str`].
 ///
@@ -111,6 +112,31 @@ pub trait StrExt {
     fn trim_end_match_opt<'a, P>(&'a self, pat: P) -> Option<&'a Self>
     where
         P: Pattern<'a, Searcher: ReverseSearcher<'a>>;
+
+    /// Compare in natural order, where runs of digits are compared by their numeric value.
+    /// See [`natural_cmp`](crate::cmp::natural_cmp).
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    ///
+    /// use gazebo::prelude::*;
+    /// assert_eq!("file2".natural_cmp("file10"), Ordering::Less);
+    /// ```
+    fn natural_cmp(&self, other: &Self) -> Ordering;
+
+    /// Compare in natural order, ignoring case. See
+    /// [`natural_cmp_case_insensitive`](crate::cmp::natural_cmp_case_insensitive).
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    ///
+    /// use gazebo::prelude::*;
+    /// assert_eq!(
+    ///     "File2".natural_cmp_case_insensitive("file10"),
+    ///     Ordering::Less
+    /// );
+    /// ```
+    fn natural_cmp_case_insensitive(&self, other: &Self) -> Ordering;
 }
 
 impl StrExt for str {
@@ -171,4 +197,12 @@ impl StrExt for str {
         #[allow(deprecated)]
         self.trim_end_match_opt(pat).unwrap_or(self)
     }
+
+    fn natural_cmp(&self, other: &Self) -> Ordering {
+        crate::cmp::natural_cmp(self, other)
+    }
+
+    fn natural_cmp_case_insensitive(&self, other: &Self) -> Ordering {
+        crate::cmp::natural_cmp_case_insensitive(self, other)
+    }
 }
@@ -7,7 +7,9 @@
  * of this source tree.
  */
 
-use std::borrow::Borrow;
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
 
 use dupe::Dupe;
 
@@ -69,7 +71,7 @@ pub trait SliceExt {
     /// );
     /// ```
     ///
-    /// This function will be generalised to [`Try`](std::ops::Try) once it has been
+    /// This function will be generalised to [`Try`](core::ops::Try) once it has been
     /// standardised.
     fn try_map<'a, B, E, F>(&'a self, f: F) -> Result<Vec<B>, E>
     where
@@ -293,7 +295,7 @@ pub trait VecExt {
     /// );
     /// ```
     ///
-    /// This function will be generalised to [`Try`](std::ops::Try) once it has been
+    /// This function will be generalised to [`Try`](core::ops::Try) once it has been
     /// standardised.
     fn into_try_map<B, E, F>(self, f: F) -> Result<Vec<B>, E>
     where
@@ -9,65 +9,1355 @@
 
 //! File/IO operations.
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
 use std::fs::create_dir_all;
-use std::fs::write;
+use std::fs::File;
+use std::fs::Metadata;
+use std::fs::OpenOptions;
+use std::fs::Permissions;
+use std::fs::ReadDir;
+use std::fs::TryLockError;
 use std::io;
+use std::io::Read;
+use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::str;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use dupe::Dupe;
+
+/// The operation which failed, recorded in a [`FileError`].
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FileOp {
+    /// Reading a file, e.g. [`read`] or [`read_to_string`].
+    Read,
+    /// Writing a file, e.g. [`write`](fn@write) or [`write_atomic`].
+    Write,
+    /// Creating the directories up to a file, e.g. [`create_dirs_and_write`].
+    CreateDirs,
+    /// [`remove_file`].
+    RemoveFile,
+    /// [`remove_dir_all`].
+    RemoveDirAll,
+    /// [`rename`].
+    Rename,
+    /// [`copy`].
+    Copy,
+    /// [`metadata`].
+    Metadata,
+    /// [`read_dir`].
+    ReadDir,
+    /// Opening or locking a [`LockedFile`] or [`DirLock`].
+    Lock,
+}
+
+impl Display for FileOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileOp::Read => "read",
+            FileOp::Write => "write",
+            FileOp::CreateDirs => "create directories",
+            FileOp::RemoveFile => "remove file",
+            FileOp::RemoveDirAll => "remove directory",
+            FileOp::Rename => "rename",
+            FileOp::Copy => "copy",
+            FileOp::Metadata => "get metadata for",
+            FileOp::ReadDir => "read directory",
+            FileOp::Lock => "lock",
+        })
+    }
+}
+
+/// An [`io::Error`] from a file operation, along with the operation and path(s) involved,
+/// so the message says which file couldn't be found. Returned by the functions in this
+/// module, and converts into an [`io::Error`] (keeping the message) for use with `?`.
+///
+/// ```
+/// let err = gazebo::file::read_to_string("does/not/exist.txt").unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+/// assert!(err
+///     .to_string()
+///     .starts_with("failed to read `does/not/exist.txt`: "));
+/// ```
+#[derive(Debug)]
+pub struct FileError {
+    op: FileOp,
+    path: PathBuf,
+    to: Option<PathBuf>,
+    error: io::Error,
+}
+
+impl FileError {
+    fn new(op: FileOp, path: &Path, error: io::Error) -> Self {
+        FileError {
+            op,
+            path: path.to_owned(),
+            to: None,
+            error,
+        }
+    }
+
+    fn new_from_to(op: FileOp, from: &Path, to: &Path, error: io::Error) -> Self {
+        FileError {
+            op,
+            path: from.to_owned(),
+            to: Some(to.to_owned()),
+            error,
+        }
+    }
+
+    /// The operation which failed.
+    pub fn op(&self) -> FileOp {
+        self.op
+    }
+
+    /// The path the operation was on, or the source path for [`rename`] and [`copy`].
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The destination path for [`rename`] and [`copy`].
+    pub fn to(&self) -> Option<&Path> {
+        self.to.as_deref()
+    }
+
+    /// The kind of the underlying [`io::Error`].
+    pub fn kind(&self) -> io::ErrorKind {
+        self.error.kind()
+    }
+
+    /// The underlying [`io::Error`], without the path.
+    pub fn io_error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Extract the underlying [`io::Error`], without the path.
+    pub fn into_io_error(self) -> io::Error {
+        self.error
+    }
+}
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to {} `{}`", self.op, self.path.display())?;
+        if let Some(to) = &self.to {
+            write!(f, " to `{}`", to.display())?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+impl Error for FileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<FileError> for io::Error {
+    fn from(e: FileError) -> Self {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+/// Like [`fs::read`], but the error includes the path.
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, FileError> {
+    let path = path.as_ref();
+    fs::read(path).map_err(|e| FileError::new(FileOp::Read, path, e))
+}
+
+/// Like [`read`], but returns [`None`] if the file doesn't exist.
+pub fn read_if_exists<P: AsRef<Path>>(path: P) -> Result<Option<Vec<u8>>, FileError> {
+    match read(path) {
+        Ok(x) => Ok(Some(x)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Like [`fs::read_to_string`], but the error includes the path.
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String, FileError> {
+    let path = path.as_ref();
+    fs::read_to_string(path).map_err(|e| FileError::new(FileOp::Read, path, e))
+}
+
+/// Like [`fs::write`], but the error includes the path.
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<(), FileError> {
+    let path = path.as_ref();
+    fs::write(path, contents).map_err(|e| FileError::new(FileOp::Write, path, e))
+}
+
+/// Like [`fs::remove_file`], but the error includes the path.
+pub fn remove_file<P: AsRef<Path>>(path: P) -> Result<(), FileError> {
+    let path = path.as_ref();
+    fs::remove_file(path).map_err(|e| FileError::new(FileOp::RemoveFile, path, e))
+}
+
+/// Like [`fs::remove_dir_all`], but the error includes the path.
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> Result<(), FileError> {
+    let path = path.as_ref();
+    fs::remove_dir_all(path).map_err(|e| FileError::new(FileOp::RemoveDirAll, path, e))
+}
+
+/// Like [`fs::rename`], but the error includes both paths.
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<(), FileError> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    fs::rename(from, to).map_err(|e| FileError::new_from_to(FileOp::Rename, from, to, e))
+}
+
+/// Like [`fs::copy`], but the error includes both paths.
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<u64, FileError> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    fs::copy(from, to).map_err(|e| FileError::new_from_to(FileOp::Copy, from, to, e))
+}
+
+/// Like [`fs::metadata`], but the error includes the path.
+pub fn metadata<P: AsRef<Path>>(path: P) -> Result<Metadata, FileError> {
+    let path = path.as_ref();
+    fs::metadata(path).map_err(|e| FileError::new(FileOp::Metadata, path, e))
+}
+
+/// Like [`fs::read_dir`], but the error includes the path. Errors while iterating over the
+/// entries are plain [`io::Error`]s.
+pub fn read_dir<P: AsRef<Path>>(path: P) -> Result<ReadDir, FileError> {
+    let path = path.as_ref();
+    fs::read_dir(path).map_err(|e| FileError::new(FileOp::ReadDir, path, e))
+}
 
 /// A simple api for creating all the directories up to a path for a file, and
 /// then writing the contents to that file.
 pub fn create_dirs_and_write<P: AsRef<Path>, C: AsRef<[u8]>>(
     path: P,
     contents: C,
-) -> io::Result<()> {
+) -> Result<(), FileError> {
+    let path = path.as_ref();
+    create_parent_dirs(path)?;
+    write(path, contents)
+}
+
+fn create_parent_dirs(path: &Path) -> Result<(), FileError> {
     // no parent means no directory component, and we can directly write to that
     // file
-    path.as_ref().parent().map_or(Ok(()), create_dir_all)?;
+    match path.parent() {
+        Some(dir) => create_dir_all(dir).map_err(|e| FileError::new(FileOp::CreateDirs, dir, e)),
+        None => Ok(()),
+    }
+}
 
-    write(path.as_ref(), contents)
+/// Whether a file was written, from [`write_if_changed`] and friends.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, Hash)]
+pub enum WriteOutcome {
+    /// The file didn't exist, or had different contents, so was written.
+    Written,
+    /// The file already had the given contents, so was left untouched.
+    Unchanged,
+}
+
+/// Write the contents to a file, unless it already has exactly those contents, in which
+/// case the file (and its modification time) is left alone. Useful for generated files
+/// consumed by build systems, which would otherwise rebuild everything downstream.
+///
+/// The existing file is compared in chunks, so large files aren't read into memory.
+pub fn write_if_changed<P: AsRef<Path>, C: AsRef<[u8]>>(
+    path: P,
+    contents: C,
+) -> Result<WriteOutcome, FileError> {
+    let path = path.as_ref();
+    let contents = contents.as_ref();
+    if has_contents(path, contents)? {
+        return Ok(WriteOutcome::Unchanged);
+    }
+    write(path, contents)?;
+    Ok(WriteOutcome::Written)
+}
+
+/// Like [`write_if_changed`], but creating all the directories up to the file, like
+/// [`create_dirs_and_write`].
+pub fn create_dirs_and_write_if_changed<P: AsRef<Path>, C: AsRef<[u8]>>(
+    path: P,
+    contents: C,
+) -> Result<WriteOutcome, FileError> {
+    let path = path.as_ref();
+    create_parent_dirs(path)?;
+    write_if_changed(path, contents)
+}
+
+/// Does the file at `path` exist and contain exactly `contents`.
+fn has_contents(path: &Path, contents: &[u8]) -> Result<bool, FileError> {
+    has_contents_impl(path, contents).map_err(|e| FileError::new(FileOp::Read, path, e))
+}
+
+fn has_contents_impl(path: &Path, mut contents: &[u8]) -> io::Result<bool> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if file.metadata()?.len() != contents.len() as u64 {
+        return Ok(false);
+    }
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let n = match file.read(&mut buffer) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if n == 0 {
+            return Ok(contents.is_empty());
+        }
+        match contents.strip_prefix(&buffer[..n]) {
+            Some(rest) => contents = rest,
+            None => return Ok(false),
+        }
+    }
+}
+
+/// Like [`create_dirs_and_write`], but writes the file atomically, see [`write_atomic`].
+pub fn create_dirs_and_write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(
+    path: P,
+    contents: C,
+) -> Result<(), FileError> {
+    AtomicWriteOptions::new()
+        .create_dirs(true)
+        .write(path, contents)
+}
+
+/// Write the contents to a file atomically, so readers either see the old contents or the
+/// new contents, and a crash never leaves a partially written file. The contents are
+/// written to a temporary file next to the target, which is synced to disk and then
+/// renamed over the target. Use [`AtomicWriteOptions`] to control the permissions.
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<(), FileError> {
+    AtomicWriteOptions::new().write(path, contents)
+}
+
+/// Options for writing a file atomically, in the style of [`OpenOptions`].
+///
+/// ```
+/// use gazebo::file::AtomicWriteOptions;
+///
+/// # let dir = std::env::temp_dir().join(format!("gazebo_doc_{}", std::process::id()));
+/// let path = dir.join("out/result.txt");
+/// AtomicWriteOptions::new()
+///     .create_dirs(true)
+///     .preserve_permissions(true)
+///     .write(&path, "contents")?;
+/// assert_eq!(std::fs::read_to_string(&path)?, "contents");
+/// # std::fs::remove_dir_all(dir)?;
+/// # Ok::<_, std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AtomicWriteOptions {
+    create_dirs: bool,
+    lock: bool,
+    preserve_permissions: bool,
+    permissions: Option<Permissions>,
+}
+
+impl AtomicWriteOptions {
+    /// The default options, which don't create directories and give the file the same
+    /// permissions as [`fs::write`] would for a new file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create all the directories up to the file, like [`create_dirs_and_write`].
+    pub fn create_dirs(&mut self, create_dirs: bool) -> &mut Self {
+        self.create_dirs = create_dirs;
+        self
+    }
+
+    /// Hold an exclusive lock on `.{file_name}.lock` next to the file while writing, so
+    /// concurrent writers of the same file using this option don't interleave. With
+    /// [`write_if_changed`](AtomicWriteOptions::write_if_changed), the comparison with the
+    /// existing contents also happens under the lock. On Unix the lock file is removed
+    /// again afterwards.
+    pub fn lock(&mut self, lock: bool) -> &mut Self {
+        self.lock = lock;
+        self
+    }
+
+    /// If the file already exists, give the new file the same permissions. Takes priority
+    /// over [`permissions`](AtomicWriteOptions::permissions) when the file exists.
+    pub fn preserve_permissions(&mut self, preserve_permissions: bool) -> &mut Self {
+        self.preserve_permissions = preserve_permissions;
+        self
+    }
+
+    /// Set the permissions of the file.
+    pub fn permissions(&mut self, permissions: Permissions) -> &mut Self {
+        self.permissions = Some(permissions);
+        self
+    }
+
+    /// Write the contents to the file at `path` with these options.
+    pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(
+        &self,
+        path: P,
+        contents: C,
+    ) -> Result<(), FileError> {
+        let path = path.as_ref();
+        let dir = self.prepare_dir(path)?;
+        let _lock = self.lock_target(path, dir)?;
+        self.write_unlocked(path, dir, contents.as_ref())
+    }
+
+    /// Like [`write`](AtomicWriteOptions::write), but leaves the file untouched if it
+    /// already has exactly the given contents, see [`write_if_changed`]. The permissions
+    /// aren't changed for an unchanged file.
+    pub fn write_if_changed<P: AsRef<Path>, C: AsRef<[u8]>>(
+        &self,
+        path: P,
+        contents: C,
+    ) -> Result<WriteOutcome, FileError> {
+        let path = path.as_ref();
+        let contents = contents.as_ref();
+        let dir = self.prepare_dir(path)?;
+        let _lock = self.lock_target(path, dir)?;
+        if has_contents(path, contents)? {
+            return Ok(WriteOutcome::Unchanged);
+        }
+        self.write_unlocked(path, dir, contents)?;
+        Ok(WriteOutcome::Written)
+    }
+
+    /// The directory containing `path`, created if requested.
+    fn prepare_dir<'a>(&self, path: &'a Path) -> Result<&'a Path, FileError> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if self.create_dirs {
+            create_dir_all(dir).map_err(|e| FileError::new(FileOp::CreateDirs, dir, e))?;
+        }
+        Ok(dir)
+    }
+
+    fn lock_target(&self, path: &Path, dir: &Path) -> Result<Option<RemovableLock>, FileError> {
+        if !self.lock {
+            return Ok(None);
+        }
+        let name = path.file_name().ok_or_else(|| {
+            FileError::new(
+                FileOp::Lock,
+                path,
+                io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"),
+            )
+        })?;
+        let mut lock_name = OsString::from(".");
+        lock_name.push(name);
+        lock_name.push(".lock");
+        RemovableLock::lock(&dir.join(lock_name), LockMode::Exclusive, None)
+    }
+
+    fn write_unlocked(&self, path: &Path, dir: &Path, contents: &[u8]) -> Result<(), FileError> {
+        let existing = if self.preserve_permissions {
+            match metadata(path) {
+                Ok(meta) => Some(meta.permissions()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            }
+        } else {
+            None
+        };
+        let permissions = existing.or_else(|| self.permissions.clone());
+        write_via_temp(path, dir, contents, permissions)
+            .map_err(|e| FileError::new(FileOp::Write, path, e))
+    }
+}
+
+fn write_via_temp(
+    path: &Path,
+    dir: &Path,
+    contents: &[u8],
+    permissions: Option<Permissions>,
+) -> io::Result<()> {
+    let (temp, mut file) = TempFile::create(path)?;
+    file.write_all(contents)?;
+    if let Some(permissions) = permissions {
+        file.set_permissions(permissions)?;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp.0, path)?;
+    temp.keep();
+    sync_dir(dir)
+}
+
+/// A uniquely named temporary file next to a target path, deleted on drop unless kept.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn create(path: &Path) -> io::Result<(TempFile, File)> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+        loop {
+            let mut temp_name = OsString::from(".");
+            temp_name.push(name);
+            temp_name.push(format!(".{}.tmp", unique_suffix()));
+            let temp = path.with_file_name(temp_name);
+            match OpenOptions::new().write(true).create_new(true).open(&temp) {
+                Ok(file) => return Ok((TempFile(temp), file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn keep(self) {
+        std::mem::forget(self)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // Best effort, we are already reporting the original error
+        let _ignore = fs::remove_file(&self.0);
+    }
+}
+
+/// A suffix which is unique within this process, and unlikely to clash with other processes.
+fn unique_suffix() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "{}.{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Sync a directory, so that a rename within it is durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Directories can't be opened for syncing on this platform, and renames are already durable.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Whether a lock can be shared with other readers, or is held exclusively.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, Hash)]
+pub enum LockMode {
+    /// Any number of shared locks can be held at once, but not alongside an exclusive lock.
+    Shared,
+    /// Only one exclusive lock can be held at once.
+    Exclusive,
+}
+
+/// An advisory lock on a file, released when dropped. Uses [`File::lock`], which is `flock`
+/// on Linux, so only excludes other processes (or threads) that also take the lock.
+///
+/// The file is created if it doesn't exist, and is not removed afterwards.
+///
+/// ```
+/// use gazebo::file::LockMode;
+/// use gazebo::file::LockedFile;
+/// use gazebo::file::TempDir;
+///
+/// let dir = TempDir::new()?;
+/// let path = dir.path().join("cache.lock");
+/// let lock = LockedFile::lock(&path, LockMode::Exclusive)?;
+/// assert!(LockedFile::try_lock(&path, LockMode::Shared)?.is_none());
+/// drop(lock);
+/// assert!(LockedFile::try_lock(&path, LockMode::Shared)?.is_some());
+/// # Ok::<_, std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct LockedFile {
+    file: File,
+    path: PathBuf,
+    mode: LockMode,
+}
+
+impl LockedFile {
+    fn open(path: &Path) -> Result<File, FileError> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| FileError::new(FileOp::Lock, path, e))
+    }
+
+    /// Lock the file at `path`, blocking until the lock is available.
+    pub fn lock<P: AsRef<Path>>(path: P, mode: LockMode) -> Result<LockedFile, FileError> {
+        let path = path.as_ref();
+        let file = Self::open(path)?;
+        match mode {
+            LockMode::Shared => file.lock_shared(),
+            LockMode::Exclusive => file.lock(),
+        }
+        .map_err(|e| FileError::new(FileOp::Lock, path, e))?;
+        Ok(LockedFile {
+            file,
+            path: path.to_owned(),
+            mode,
+        })
+    }
+
+    /// Lock the file at `path` if the lock is available right now, otherwise return [`None`].
+    pub fn try_lock<P: AsRef<Path>>(
+        path: P,
+        mode: LockMode,
+    ) -> Result<Option<LockedFile>, FileError> {
+        Self::lock_timeout(path, mode, Duration::ZERO)
+    }
+
+    /// Lock the file at `path`, waiting at most `timeout` for the lock to become available,
+    /// otherwise return [`None`].
+    pub fn lock_timeout<P: AsRef<Path>>(
+        path: P,
+        mode: LockMode,
+        timeout: Duration,
+    ) -> Result<Option<LockedFile>, FileError> {
+        let path = path.as_ref();
+        let file = Self::open(path)?;
+        let deadline = Instant::now() + timeout;
+        let mut delay = Duration::from_millis(1);
+        loop {
+            let res = match mode {
+                LockMode::Shared => file.try_lock_shared(),
+                LockMode::Exclusive => file.try_lock(),
+            };
+            match res {
+                Ok(()) => {
+                    return Ok(Some(LockedFile {
+                        file,
+                        path: path.to_owned(),
+                        mode,
+                    }));
+                }
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(e)) => return Err(FileError::new(FileOp::Lock, path, e)),
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(delay.min(deadline - now));
+            delay = (delay * 2).min(Duration::from_millis(50));
+        }
+    }
+
+    /// The locked file.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// The path of the locked file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// How the file is locked.
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+}
+
+impl Drop for LockedFile {
+    fn drop(&mut self) {
+        // Closing the file would release the lock anyway, so ignore errors
+        let _ignore = self.file.unlock();
+    }
+}
+
+/// A [`LockedFile`] which is removed when an exclusive lock is released, so lock files
+/// don't accumulate. Only works if everyone locking the file goes through this type.
+#[derive(Debug)]
+struct RemovableLock(LockedFile);
+
+impl RemovableLock {
+    /// Lock `path`, waiting at most `timeout` if given. If the previous holder removed the
+    /// file before we got the lock, we'd hold a lock nobody else can see, so try again.
+    fn lock(
+        path: &Path,
+        mode: LockMode,
+        timeout: Option<Duration>,
+    ) -> Result<Option<RemovableLock>, FileError> {
+        let deadline = timeout.map(|x| Instant::now() + x);
+        loop {
+            let lock = match deadline {
+                None => LockedFile::lock(path, mode)?,
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    match LockedFile::lock_timeout(path, mode, timeout)? {
+                        Some(lock) => lock,
+                        None => return Ok(None),
+                    }
+                }
+            };
+            if Self::is_linked(&lock).map_err(|e| FileError::new(FileOp::Lock, path, e))? {
+                return Ok(Some(RemovableLock(lock)));
+            }
+        }
+    }
+
+    /// Whether the path of the lock still refers to the file we locked.
+    #[cfg(unix)]
+    fn is_linked(lock: &LockedFile) -> io::Result<bool> {
+        use std::os::unix::fs::MetadataExt;
+
+        let locked = lock.file().metadata()?;
+        match fs::metadata(lock.path()) {
+            Ok(meta) => Ok(meta.dev() == locked.dev() && meta.ino() == locked.ino()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Without Unix we never remove the file, so it is always still there.
+    #[cfg(not(unix))]
+    fn is_linked(_lock: &LockedFile) -> io::Result<bool> {
+        Ok(true)
+    }
+}
+
+impl Drop for RemovableLock {
+    fn drop(&mut self) {
+        // Only an exclusive holder knows nobody else is using the file. Anyone already
+        // waiting for it will see it has gone once they get the lock, and try again.
+        if cfg!(unix) && self.0.mode() == LockMode::Exclusive {
+            let _ignore = fs::remove_file(self.0.path());
+        }
+    }
+}
+
+/// An advisory lock on a directory, held through a [`LockedFile`] named
+/// [`DirLock::FILE_NAME`] inside it, and released when dropped. On Unix, releasing an
+/// exclusive lock also removes the lock file, while shared locks leave it in place since
+/// other readers may still be holding it. Use [`AtomicWriteOptions::lock`] to lock
+/// individual files while writing them.
+#[derive(Debug)]
+pub struct DirLock(RemovableLock);
+
+impl DirLock {
+    /// The name of the lock file created inside the directory.
+    pub const FILE_NAME: &'static str = ".lock";
+
+    /// Lock the directory, blocking until the lock is available.
+    pub fn lock<P: AsRef<Path>>(dir: P, mode: LockMode) -> Result<DirLock, FileError> {
+        let lock = RemovableLock::lock(&dir.as_ref().join(Self::FILE_NAME), mode, None)?;
+        Ok(DirLock(
+            lock.expect("locks without a timeout always succeed"),
+        ))
+    }
+
+    /// Lock the directory if the lock is available right now, otherwise return [`None`].
+    pub fn try_lock<P: AsRef<Path>>(dir: P, mode: LockMode) -> Result<Option<DirLock>, FileError> {
+        Self::lock_timeout(dir, mode, Duration::ZERO)
+    }
+
+    /// Lock the directory, waiting at most `timeout` for the lock to become available,
+    /// otherwise return [`None`].
+    pub fn lock_timeout<P: AsRef<Path>>(
+        dir: P,
+        mode: LockMode,
+        timeout: Duration,
+    ) -> Result<Option<DirLock>, FileError> {
+        Ok(
+            RemovableLock::lock(&dir.as_ref().join(Self::FILE_NAME), mode, Some(timeout))?
+                .map(DirLock),
+        )
+    }
+
+    /// The underlying lock file.
+    pub fn locked_file(&self) -> &LockedFile {
+        &self.0 .0
+    }
+}
+
+/// A temporary directory, which is deleted (with everything in it) when dropped. Intended
+/// for tests, together with [`FileTree`].
+///
+/// The directory is created inside `gazebo` in [`std::env::temp_dir`]. Call
+/// [`keep`](TempDir::keep) to leave it in place, e.g. to inspect after a failing test.
+///
+/// ```
+/// use gazebo::file::assert_tree_eq;
+/// use gazebo::file::TempDir;
+/// use gazebo::tree;
+///
+/// let dir = TempDir::new()?;
+/// tree! { "src/lib.rs" => "", "target/" => {} }.write_to(&dir)?;
+/// std::fs::write(dir.path().join("src/lib.rs"), "pub mod foo;")?;
+/// assert_tree_eq(
+///     &dir,
+///     &tree! {
+///         "src" => { "lib.rs" => "pub mod foo;" },
+///         "target/" => {},
+///     },
+/// );
+/// # Ok::<_, std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a new, empty, uniquely named temporary directory.
+    pub fn new() -> io::Result<TempDir> {
+        // We don't delete $TMP/gazebo because someone else might be using that
+        let parent = std::env::temp_dir().join("gazebo");
+        create_dir_all(&parent)?;
+        loop {
+            let path = parent.join(unique_suffix());
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(TempDir(path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// The path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Don't delete the directory, returning its path.
+    pub fn keep(self) -> PathBuf {
+        let path = self.0.clone();
+        std::mem::forget(self);
+        path
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // Delete the path, ignore errors
+        let _ignore = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A description of files and directories, relative to some root, usually built with
+/// [`tree!`](crate::tree!). Files and directories are named by `/` separated paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileTree {
+    files: BTreeMap<String, Vec<u8>>,
+    dirs: BTreeSet<String>,
+}
+
+impl FileTree {
+    /// An empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file with the given contents. Directories up to the file are implied.
+    pub fn file(&mut self, path: impl AsRef<str>, contents: impl AsRef<[u8]>) -> &mut Self {
+        self.files.insert(
+            normalize(path.as_ref()).to_owned(),
+            contents.as_ref().to_vec(),
+        );
+        self
+    }
+
+    /// Add a directory containing the given tree, which may be empty.
+    pub fn dir(&mut self, path: impl AsRef<str>, tree: FileTree) -> &mut Self {
+        let path = normalize(path.as_ref());
+        let join = |x: String| {
+            if path.is_empty() {
+                x
+            } else {
+                format!("{}/{}", path, x)
+            }
+        };
+        self.dirs.insert(path.to_owned());
+        self.dirs.extend(tree.dirs.into_iter().map(join));
+        self.files
+            .extend(tree.files.into_iter().map(|(k, v)| (join(k), v)));
+        self
+    }
+
+    /// Create the files and directories under `root`, overwriting any existing files.
+    pub fn write_to(&self, root: impl AsRef<Path>) -> io::Result<()> {
+        let root = root.as_ref();
+        for dir in &self.dirs {
+            create_dir_all(root.join(dir))?;
+        }
+        for (path, contents) in &self.files {
+            create_dirs_and_write(root.join(path), contents)?;
+        }
+        Ok(())
+    }
+
+    /// Read all the files and directories under `root`.
+    pub fn read(root: impl AsRef<Path>) -> io::Result<FileTree> {
+        fn go(tree: &mut FileTree, dir: &Path, prefix: &str) -> io::Result<()> {
+            tree.dirs.insert(prefix.to_owned());
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                let path = if prefix.is_empty() {
+                    name.into_owned()
+                } else {
+                    format!("{}/{}", prefix, name)
+                };
+                if entry.file_type()?.is_dir() {
+                    go(tree, &entry.path(), &path)?;
+                } else {
+                    tree.files.insert(path, fs::read(entry.path())?);
+                }
+            }
+            Ok(())
+        }
+
+        let mut tree = FileTree::new();
+        go(&mut tree, root.as_ref(), "")?;
+        Ok(tree)
+    }
+
+    /// Directories which contain nothing else in the tree. Other directories are implied
+    /// by their contents, so only these matter when comparing trees.
+    fn empty_dirs(&self) -> BTreeSet<&str> {
+        self.dirs
+            .iter()
+            .map(|x| x.as_str())
+            .filter(|dir| {
+                !dir.is_empty()
+                    && !self
+                        .dirs
+                        .iter()
+                        .chain(self.files.keys())
+                        .any(|x| is_parent(dir, x))
+            })
+            .collect()
+    }
+
+    /// Describe the differences from `actual` to `self`, or `None` if they are the same.
+    fn diff(&self, actual: &FileTree) -> Option<String> {
+        let mut res = String::new();
+        for (path, contents) in &self.files {
+            match actual.files.get(path) {
+                None => res.push_str(&format!("missing file: {}\n", path)),
+                Some(x) if x != contents => res.push_str(&format!(
+                    "different contents: {}\n  expected: {:?}\n  actual:   {:?}\n",
+                    path,
+                    String::from_utf8_lossy(contents),
+                    String::from_utf8_lossy(x)
+                )),
+                Some(_) => {}
+            }
+        }
+        for path in actual.files.keys() {
+            if !self.files.contains_key(path) {
+                res.push_str(&format!("unexpected file: {}\n", path));
+            }
+        }
+        let (expected, actual) = (self.empty_dirs(), actual.empty_dirs());
+        for dir in expected.difference(&actual) {
+            res.push_str(&format!("missing empty directory: {}/\n", dir));
+        }
+        for dir in actual.difference(&expected) {
+            res.push_str(&format!("unexpected empty directory: {}/\n", dir));
+        }
+        if res.is_empty() {
+            None
+        } else {
+            Some(res)
+        }
+    }
+}
+
+impl Display for FileTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (path, contents) in &self.files {
+            match str::from_utf8(contents) {
+                Ok(contents) => writeln!(f, "{} => {:?}", path, contents)?,
+                Err(_) => writeln!(f, "{} => <{} bytes>", path, contents.len())?,
+            }
+        }
+        for dir in self.empty_dirs() {
+            writeln!(f, "{}/", dir)?;
+        }
+        Ok(())
+    }
+}
+
+fn normalize(path: &str) -> &str {
+    path.trim_matches('/')
+}
+
+/// Is `parent` a strict parent directory of `child`.
+fn is_parent(parent: &str, child: &str) -> bool {
+    child.len() > parent.len()
+        && child.starts_with(parent)
+        && child.as_bytes()[parent.len()] == b'/'
+}
+
+/// Assert that the directory `root` contains exactly the files and directories in
+/// `expected`, panicking with a description of the differences if not.
+#[track_caller]
+pub fn assert_tree_eq(root: impl AsRef<Path>, expected: &FileTree) {
+    let root = root.as_ref();
+    let actual = match FileTree::read(root) {
+        Ok(actual) => actual,
+        Err(e) => panic!("failed to read directory `{}`: {}", root.display(), e),
+    };
+    if let Some(diff) = expected.diff(&actual) {
+        panic!(
+            "directory `{}` doesn't match the expected tree:\n{}",
+            root.display(),
+            diff
+        );
+    }
+}
+
+/// Build a [`FileTree`](crate::file::FileTree) from `path => contents` pairs, where the
+/// contents are either an expression producing bytes (e.g. a string) for a file, or
+/// `{ ... }` containing a nested tree for a directory.
+///
+/// ```
+/// use gazebo::tree;
+///
+/// let tree = tree! {
+///     "a/b.txt" => "hi",
+///     "c/" => {},
+///     "d" => { "e.txt" => "bye" },
+/// };
+/// assert_eq!(
+///     tree.to_string(),
+///     "a/b.txt => \"hi\"\nd/e.txt => \"bye\"\nc/\n"
+/// );
+/// ```
+#[macro_export]
+macro_rules! tree {
+    (@acc $tree:ident;) => {};
+    (@acc $tree:ident; $path:expr => { $($inner:tt)* } $(, $($rest:tt)*)?) => {
+        $tree.dir($path, $crate::tree! { $($inner)* });
+        $crate::tree!(@acc $tree; $($($rest)*)?);
+    };
+    (@acc $tree:ident; $path:expr => $contents:expr $(, $($rest:tt)*)?) => {
+        $tree.file($path, $contents);
+        $crate::tree!(@acc $tree; $($($rest)*)?);
+    };
+    ($($x:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut tree = $crate::file::FileTree::new();
+        $crate::tree!(@acc tree; $($x)*);
+        tree
+    }};
 }
 
 #[cfg(test)]
 mod tests {
     use std::fs;
-    use std::path::PathBuf;
-    use std::time::SystemTime;
 
     use super::*;
 
-    // We'd rather use something like tempdir, but keep the dependencies down
-    struct RemoveDir(PathBuf);
+    #[test]
+    fn test_create_all_and_write() -> io::Result<()> {
+        let temp = TempDir::new()?;
+        let file = temp.path().join("foo/bar");
+        create_dirs_and_write(&file, "contents")?;
+        let contents = fs::read_to_string(file)?;
+        assert_eq!(contents, "contents");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_atomic() -> io::Result<()> {
+        let temp = TempDir::new()?;
 
-    impl Drop for RemoveDir {
-        fn drop(&mut self) {
-            // Delete the path, ignore errors
-            let _ignore = fs::remove_dir_all(&self.0);
-        }
+        let file = temp.path().join("foo/bar");
+        assert!(write_atomic(&file, "contents").is_err());
+        create_dirs_and_write_atomic(&file, "contents")?;
+        assert_eq!(fs::read_to_string(&file)?, "contents");
+        write_atomic(&file, "new contents")?;
+        assert_eq!(fs::read_to_string(&file)?, "new contents");
+
+        // No temporary files are left behind
+        let entries: Vec<_> = fs::read_dir(temp.path().join("foo"))?
+            .map(|e| e.map(|e| e.file_name()))
+            .collect::<io::Result<_>>()?;
+        assert_eq!(entries, ["bar"]);
+        Ok(())
     }
 
-    fn random_part() -> String {
-        match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-            Ok(x) => x.as_nanos().to_string(),
-            Err(_) => "no_random_part".to_owned(),
-        }
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_permissions() -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new()?;
+        let file = temp.path().join("script.sh");
+        let mode = |path: &Path| -> io::Result<u32> {
+            Ok(fs::metadata(path)?.permissions().mode() & 0o777)
+        };
+
+        AtomicWriteOptions::new()
+            .create_dirs(true)
+            .permissions(Permissions::from_mode(0o755))
+            .write(&file, "#!/bin/sh")?;
+        assert_eq!(mode(&file)?, 0o755);
+
+        AtomicWriteOptions::new()
+            .preserve_permissions(true)
+            .permissions(Permissions::from_mode(0o600))
+            .write(&file, "#!/bin/bash")?;
+        assert_eq!(mode(&file)?, 0o755);
+        assert_eq!(fs::read_to_string(&file)?, "#!/bin/bash");
+
+        write_atomic(&file, "")?;
+        assert_ne!(mode(&file)?, 0o755);
+        Ok(())
     }
 
     #[test]
-    fn test_create_all_and_write() -> io::Result<()> {
-        // Make sure we create the directory with unique entropy in it (so no clashes)
-        // and clean up after ourselves (so we don't leak files)
-        let temp = std::env::temp_dir();
-        let temp = temp.join("gazebo");
-        let temp = temp.join(random_part());
-        // We don't delete $TMP/gazebo because someone else might be using that
-        let remove_dir = RemoveDir(temp.clone());
+    fn test_write_if_changed() -> io::Result<()> {
+        let temp = TempDir::new()?;
+        let file = temp.path().join("foo/generated.rs");
+        let modified = |path: &Path| fs::metadata(path)?.modified();
 
-        let temp = temp.join("foo/bar");
-        create_dirs_and_write(&temp, "contents")?;
-        let contents = fs::read_to_string(temp)?;
-        assert_eq!(contents, "contents");
+        assert_eq!(
+            create_dirs_and_write_if_changed(&file, "fn main() {}")?,
+            WriteOutcome::Written
+        );
+        let before = modified(&file)?;
+        assert_eq!(
+            write_if_changed(&file, "fn main() {}")?,
+            WriteOutcome::Unchanged
+        );
+        assert_eq!(
+            AtomicWriteOptions::new().write_if_changed(&file, "fn main() {}")?,
+            WriteOutcome::Unchanged
+        );
+        assert_eq!(modified(&file)?, before);
+
+        assert_eq!(
+            write_if_changed(&file, "fn main() { }")?,
+            WriteOutcome::Written
+        );
+        assert_eq!(
+            AtomicWriteOptions::new().write_if_changed(&file, "")?,
+            WriteOutcome::Written
+        );
+        assert_eq!(write_if_changed(&file, "")?, WriteOutcome::Unchanged);
+        assert_eq!(fs::read_to_string(&file)?, "");
+
+        // Larger than the comparison buffer, differing only at the end
+        let mut big = vec![7u8; 200 * 1024];
+        write_if_changed(&file, &big)?;
+        assert_eq!(write_if_changed(&file, &big)?, WriteOutcome::Unchanged);
+        *big.last_mut().unwrap() = 8;
+        assert_eq!(write_if_changed(&file, &big)?, WriteOutcome::Written);
+        assert_eq!(fs::read(&file)?, big);
+        Ok(())
+    }
+
+    #[test]
+    fn test_temp_dir() -> io::Result<()> {
+        let temp = TempDir::new()?;
+        let path = temp.path().to_owned();
+        assert!(path.is_dir());
+        assert_ne!(path, TempDir::new()?.path());
+        drop(temp);
+        assert!(!path.exists());
+
+        let kept = TempDir::new()?.keep();
+        assert!(kept.is_dir());
+        fs::remove_dir(kept)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree() -> io::Result<()> {
+        let temp = TempDir::new()?;
+        let expected = crate::tree! {
+            "a/b.txt" => "hi",
+            "c/" => {},
+            "d" => {
+                "e" => { "f.bin" => [0u8, 255] },
+                "g/" => {},
+            },
+        };
+        expected.write_to(&temp)?;
+        assert_eq!(fs::read_to_string(temp.path().join("a/b.txt"))?, "hi");
+        assert!(temp.path().join("d/g").is_dir());
+        assert_tree_eq(&temp, &expected);
+
+        // Directories are implied by their contents
+        let mut implied = expected.clone();
+        implied
+            .dir("a", FileTree::new())
+            .dir("d/e", FileTree::new());
+        assert_tree_eq(&temp, &implied);
+        assert_eq!(FileTree::read(&temp)?.to_string(), expected.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_diff() -> io::Result<()> {
+        let temp = TempDir::new()?;
+        crate::tree! { "a.txt" => "x", "b.txt" => "y", "c/" => {} }.write_to(&temp)?;
+        let expected = crate::tree! { "a.txt" => "z", "d.txt" => "", "e/" => {} };
+        let diff = expected.diff(&FileTree::read(&temp)?).unwrap();
+        assert_eq!(
+            diff,
+            "different contents: a.txt\n  expected: \"z\"\n  actual:   \"x\"\n\
+             missing file: d.txt\n\
+             unexpected file: b.txt\n\
+             missing empty directory: e/\n\
+             unexpected empty directory: c/\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "missing file: b.txt")]
+    fn test_assert_tree_eq_fails() {
+        let temp = TempDir::new().unwrap();
+        assert_tree_eq(&temp, &crate::tree! { "b.txt" => "" });
+    }
+
+    #[test]
+    fn test_file_error() -> io::Result<()> {
+        let temp = TempDir::new()?;
+        let missing = temp.path().join("missing.txt");
+        let err = read(&missing).unwrap_err();
+        assert_eq!(err.op(), FileOp::Read);
+        assert_eq!(err.path(), missing);
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            err.to_string(),
+            format!("failed to read `{}`: {}", missing.display(), err.io_error())
+        );
+        assert!(err.source().is_some());
+
+        let err = rename(&missing, temp.path().join("b.txt")).unwrap_err();
+        assert_eq!(err.to(), Some(temp.path().join("b.txt").as_path()));
+        assert!(err
+            .to_string()
+            .contains(&format!("to `{}`", temp.path().join("b.txt").display())));
+
+        let io_err: io::Error = remove_file(&missing).unwrap_err().into();
+        assert_eq!(io_err.kind(), io::ErrorKind::NotFound);
+        assert!(io_err.to_string().starts_with("failed to remove file `"));
+
+        // A file where a directory is needed
+        write(temp.path().join("file"), "")?;
+        let err = create_dirs_and_write(temp.path().join("file/a/b"), "").unwrap_err();
+        assert_eq!(err.op(), FileOp::CreateDirs);
+        assert_eq!(err.path(), temp.path().join("file/a"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_wrappers() -> io::Result<()> {
+        let temp = TempDir::new()?;
+        let a = temp.path().join("a.txt");
+        assert_eq!(read_if_exists(&a)?, None);
+        write(&a, "hello")?;
+        assert_eq!(read_if_exists(&a)?, Some(b"hello".to_vec()));
+        assert_eq!(copy(&a, temp.path().join("b.txt"))?, 5);
+        rename(&a, temp.path().join("c.txt"))?;
+        assert_eq!(read_to_string(temp.path().join("c.txt"))?, "hello");
+        assert_eq!(metadata(temp.path().join("b.txt"))?.len(), 5);
+        assert_eq!(read_dir(temp.path())?.count(), 2);
+        remove_file(temp.path().join("b.txt"))?;
+        create_dirs_and_write(temp.path().join("d/e"), "")?;
+        remove_dir_all(temp.path().join("d"))?;
+        assert_tree_eq(&temp, &crate::tree! { "c.txt" => "hello" });
+        Ok(())
+    }
+
+    #[test]
+    fn test_locked_file() -> io::Result<()> {
+        let temp = TempDir::new()?;
+        let path = temp.path().join("a.lock");
+
+        let shared = LockedFile::lock(&path, LockMode::Shared)?;
+        assert_eq!(shared.mode(), LockMode::Shared);
+        let shared2 = LockedFile::try_lock(&path, LockMode::Shared)?;
+        assert!(shared2.is_some());
+        assert!(LockedFile::try_lock(&path, LockMode::Exclusive)?.is_none());
+        drop((shared, shared2));
+
+        let exclusive = LockedFile::try_lock(&path, LockMode::Exclusive)?.unwrap();
+        let start = Instant::now();
+        assert!(
+            LockedFile::lock_timeout(&path, LockMode::Shared, Duration::from_millis(20))?.is_none()
+        );
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        // Released from another thread while we wait
+        let path2 = path.clone();
+        let waiter = thread::spawn(move || {
+            LockedFile::lock_timeout(&path2, LockMode::Exclusive, Duration::from_secs(60))
+                .map(|x| x.is_some())
+        });
+        thread::sleep(Duration::from_millis(10));
+        drop(exclusive);
+        assert!(waiter.join().unwrap()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dir_lock_write() -> io::Result<()> {
+        let temp = TempDir::new()?;
+        let lock = DirLock::lock(&temp, LockMode::Exclusive)?;
+        assert_eq!(
+            lock.locked_file().path(),
+            temp.path().join(DirLock::FILE_NAME)
+        );
+        assert!(DirLock::try_lock(&temp, LockMode::Shared)?.is_none());
+        // Waiting for a lock whose file is removed by the holder gets a fresh lock
+        let dir = temp.path().to_owned();
+        let waiter = thread::spawn(move || DirLock::lock(&dir, LockMode::Exclusive));
+        thread::sleep(Duration::from_millis(10));
+        drop(lock);
+        let lock = waiter.join().unwrap()?;
+        assert!(DirLock::try_lock(&temp, LockMode::Exclusive)?.is_none());
+        drop(lock);
 
-        drop(remove_dir);
+        let file = temp.path().join("out/counter");
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let file = file.clone();
+                thread::spawn(move || {
+                    AtomicWriteOptions::new()
+                        .create_dirs(true)
+                        .lock(true)
+                        .write_if_changed(&file, "same")
+                })
+            })
+            .collect();
+        let written = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|x| *x == WriteOutcome::Written)
+            .count();
+        // The check and write happen under the lock, so only the first thread writes
+        assert_eq!(written, 1);
+        // The lock files are removed once released
+        #[cfg(unix)]
+        assert_tree_eq(&temp, &crate::tree! { "out" => { "counter" => "same" } });
         Ok(())
     }
 }
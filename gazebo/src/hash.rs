@@ -9,54 +9,255 @@
 
 //! Utilities for working with hashes.
 
-use std::cmp;
-use std::collections::hash_map::DefaultHasher;
-use std::fmt;
-use std::fmt::Display;
-use std::hash::Hash;
-use std::hash::Hasher;
+use core::borrow::Borrow;
+use core::cmp;
+use core::fmt;
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::hash::BuildHasher;
+use core::hash::BuildHasherDefault;
+use core::hash::Hash;
+use core::hash::Hasher;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::collections::HashSet;
 
 use dupe::Dupe;
 
-/// A type `T`, but with the hash computed in advance, so hashing is O(1).
-#[derive(Dupe, Clone, Copy, PartialEq, Eq, Debug)]
-pub struct Hashed<T> {
+use crate::stable_hash::StableHash;
+use crate::stable_hash::StableHasher;
+
+/// The [`BuildHasher`] used by [`Hashed::new`]. Always [`FxBuildHasher`], whatever the
+/// features, so enabling `std` somewhere in the dependency graph doesn't change hashes.
+/// Previously this was SipHash (as `DefaultHasher`) with the `std` feature.
+pub type DefaultBuildHasher = FxBuildHasher;
+
+/// A fast, non-cryptographic hasher, as used inside `rustc`. Much faster than SipHash for
+/// small keys such as integers and short strings, but provides no protection against
+/// deliberately colliding keys. The result is rotated so the well mixed high bits of
+/// the last multiplication end up in the low bits, which hash tables use for buckets.
+#[derive(Debug, Clone, Copy, Dupe, Default)]
+pub struct FxHasher {
     hash: u64,
+}
+
+/// A [`BuildHasher`] for [`FxHasher`].
+pub type FxBuildHasher = BuildHasherDefault<FxHasher>;
+
+impl FxHasher {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+    /// As used by `rustc-hash`.
+    const FINAL_ROTATE: u32 = 26;
+
+    #[inline]
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(Self::SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    #[inline]
+    fn write(&mut self, mut bytes: &[u8]) {
+        while let Some((chunk, rest)) = bytes.split_first_chunk::<8>() {
+            self.add_to_hash(u64::from_le_bytes(*chunk));
+            bytes = rest;
+        }
+        if let Some((chunk, rest)) = bytes.split_first_chunk::<4>() {
+            self.add_to_hash(u32::from_le_bytes(*chunk) as u64);
+            bytes = rest;
+        }
+        for b in bytes {
+            self.add_to_hash(*b as u64);
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        // The low bits of a product only depend on the low bits of its inputs, so without
+        // this keys which only differ in their high bits would share their low bits.
+        self.hash.rotate_left(Self::FINAL_ROTATE)
+    }
+}
+
+/// A [`Hasher`] which uses the integer written to it as the hash, for keys which are
+/// already [`Hashed`], so a map doesn't hash them a second time. Any other writes are
+/// mixed in as by [`FxHasher`].
+#[derive(Debug, Clone, Copy, Dupe, Default)]
+pub struct IdentityHasher {
+    hash: u64,
+}
+
+/// A [`BuildHasher`] for [`IdentityHasher`].
+pub type BuildIdentityHasher = BuildHasherDefault<IdentityHasher>;
+
+impl Hasher for IdentityHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut fx = FxHasher { hash: self.hash };
+        fx.write(bytes);
+        self.hash = fx.hash;
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        // Hash tables take some bits from the top of the hash, so repeat it there.
+        self.hash = (i as u64) | ((i as u64) << 32);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.hash = i;
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for u64 {}
+    impl Sealed for u32 {}
+}
+
+/// The width of the hash stored in a [`Hashed`], either [`u64`] or [`u32`].
+pub trait HashWidth: Copy + Dupe + Eq + Hash + Debug + private::Sealed {
+    /// Reduce a full [`Hasher::finish`] result to this width.
+    fn from_u64(hash: u64) -> Self;
+}
+
+impl HashWidth for u64 {
+    #[inline]
+    fn from_u64(hash: u64) -> Self {
+        hash
+    }
+}
+
+impl HashWidth for u32 {
+    #[inline]
+    fn from_u64(hash: u64) -> Self {
+        // Fold in the high bits, which are the best mixed for multiplicative hashers.
+        (hash ^ (hash >> 32)) as u32
+    }
+}
+
+/// A type `T`, but with the hash computed in advance, so hashing is O(1).
+///
+/// The hash is computed with the [`BuildHasher`] `S`, and stored as `H`, which may be [`u32`]
+/// to save space (see [`Hashed32`]). Since `S` is part of the type, hashes from
+/// [`new_with_build_hasher`](Hashed::new_with_build_hasher) with different hashers can't
+/// be mixed up. [`new_with_hasher`](Hashed::new_with_hasher) accepts any [`Hasher`], and it
+/// is up to the caller to pass the one `S` builds.
+///
+/// ```
+/// use gazebo::hash::FxBuildHasher;
+/// use gazebo::hash::Hashed;
+/// use gazebo::hash::Hashed32;
+///
+/// let x: Hashed32<u32, FxBuildHasher> =
+///     Hashed::new_with_build_hasher(7, &FxBuildHasher::default());
+/// assert_eq!(std::mem::size_of_val(&x), 8);
+/// assert_eq!(*x.as_ref(), 7);
+/// ```
+pub struct Hashed<T, S = DefaultBuildHasher, H = u64> {
+    hash: H,
     value: T,
+    build: PhantomData<fn() -> S>,
 }
 
-#[allow(clippy::derived_hash_with_manual_eq)]
-impl<T> Hash for Hashed<T> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
+/// A [`Hashed`] storing a 32-bit hash.
+pub type Hashed32<T, S = DefaultBuildHasher> = Hashed<T, S, u32>;
+
+impl<T: Clone, S, H: HashWidth> Clone for Hashed<T, S, H> {
+    fn clone(&self) -> Self {
+        Hashed {
+            hash: self.hash,
+            value: self.value.clone(),
+            build: PhantomData,
+        }
+    }
+}
+
+impl<T: Copy, S, H: HashWidth> Copy for Hashed<T, S, H> {}
+
+impl<T: Dupe, S, H: HashWidth> Dupe for Hashed<T, S, H> {}
+
+impl<T: PartialEq, S, H: HashWidth> PartialEq for Hashed<T, S, H> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.value == other.value
+    }
+}
+
+impl<T: Eq, S, H: HashWidth> Eq for Hashed<T, S, H> {}
+
+impl<T: Debug, S, H: HashWidth> Debug for Hashed<T, S, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hashed")
+            .field("hash", &self.hash)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+impl<T, S, H: HashWidth> Hash for Hashed<T, S, H> {
+    fn hash<X: Hasher>(&self, state: &mut X) {
         self.hash.hash(state)
     }
 }
 
-impl<T: Display> Display for Hashed<T> {
+impl<T: Display, S, H> Display for Hashed<T, S, H> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.value.fmt(f)
     }
 }
 
-impl<T: PartialOrd> PartialOrd for Hashed<T> {
+impl<T: PartialOrd, S, H: HashWidth> PartialOrd for Hashed<T, S, H> {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
         self.value.partial_cmp(&other.value)
     }
 }
 
-impl<T: Ord> Ord for Hashed<T> {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+impl<T: Ord, S, H: HashWidth> Ord for Hashed<T, S, H> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
         self.value.cmp(&other.value)
     }
 }
 
-impl<T> AsRef<T> for Hashed<T> {
+impl<T, S, H> AsRef<T> for Hashed<T, S, H> {
     fn as_ref(&self) -> &T {
         &self.value
     }
 }
 
-impl<T> AsMut<T> for Hashed<T> {
+impl<T, S, H> AsMut<T> for Hashed<T, S, H> {
     fn as_mut(&mut self) -> &mut T {
         &mut self.value
     }
@@ -69,22 +270,359 @@ impl<T: Hash> From<T> for Hashed<T> {
 }
 
 impl<T: Hash> Hashed<T> {
+    /// Compute the hash of `value` using [`DefaultBuildHasher`].
     pub fn new(value: T) -> Self {
-        let mut hasher = DefaultHasher::new();
+        Self::new_with_build_hasher(value, &DefaultBuildHasher::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher, H: HashWidth> Hashed<T, S, H> {
+    /// Compute the hash of `value` using a hasher from `build`.
+    pub fn new_with_build_hasher(value: T, build: &S) -> Self {
+        Self::new_with_hasher(value, build.build_hasher())
+    }
+
+    /// Compute the hash of `value` using a fresh `hasher`. Two [`Hashed`] values
+    /// should only be compared if they were produced by the same kind of hasher,
+    /// which should be the one built by `S`.
+    pub fn new_with_hasher<X: Hasher>(value: T, mut hasher: X) -> Self {
         value.hash(&mut hasher);
         Self {
-            hash: hasher.finish(),
+            hash: H::from_u64(hasher.finish()),
             value,
+            build: PhantomData,
         }
     }
+}
 
+impl<T: StableHash> Hashed<T, StableHasher> {
+    /// Compute the hash of `value` with [`StableHasher`], so that it is the same across
+    /// platforms, Rust versions and processes, e.g. to use as a persistent key.
+    pub fn new_stable(value: T) -> Self {
+        Self {
+            hash: StableHasher::hash_one(&value),
+            value,
+            build: PhantomData,
+        }
+    }
+}
+
+impl<T, S, H> Hashed<T, S, H> {
     pub fn into(self) -> T {
         self.value
     }
 }
 
+impl<T, S, H: HashWidth> Hashed<T, S, H> {
+    /// Borrow the value, keeping the hash, e.g. to look up a `Hashed<K>` key
+    /// without hashing it again.
+    pub fn by_ref(&self) -> Hashed<&T, S, H> {
+        Hashed {
+            hash: self.hash,
+            value: &self.value,
+            build: PhantomData,
+        }
+    }
+
+    pub(crate) fn hash_value(&self) -> H {
+        self.hash
+    }
+}
+
+/// A key which can be looked up in a `HashedMap` or `HashedSet` with keys of type
+/// `Hashed<K>` where `K: Borrow<Q>`. Implemented for every [`Hashed`], which lets a
+/// `Hashed<&str>` find a `Hashed<String>` without allocating.
+pub trait HashedKey<Q: ?Sized, H> {
+    /// The precomputed hash.
+    fn stored_hash(&self) -> H;
+    /// The key, borrowed as `Q`.
+    fn key(&self) -> &Q;
+}
+
+impl<K: Borrow<Q>, Q: ?Sized, S, H: HashWidth> HashedKey<Q, H> for Hashed<K, S, H> {
+    fn stored_hash(&self) -> H {
+        self.hash
+    }
+
+    fn key(&self) -> &Q {
+        self.value.borrow()
+    }
+}
+
+impl<Q: ?Sized + Eq, H: HashWidth> PartialEq for dyn HashedKey<Q, H> + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.stored_hash() == other.stored_hash() && self.key() == other.key()
+    }
+}
+
+impl<Q: ?Sized + Eq, H: HashWidth> Eq for dyn HashedKey<Q, H> + '_ {}
+
+impl<Q: ?Sized, H: HashWidth> Hash for dyn HashedKey<Q, H> + '_ {
+    fn hash<X: Hasher>(&self, state: &mut X) {
+        // Must match the `Hash` for `Hashed`.
+        self.stored_hash().hash(state)
+    }
+}
+
+impl<'a, K: Borrow<Q> + 'a, Q: ?Sized + 'a, S: 'a, H: HashWidth + 'a>
+    Borrow<dyn HashedKey<Q, H> + 'a> for Hashed<K, S, H>
+{
+    fn borrow(&self) -> &(dyn HashedKey<Q, H> + 'a) {
+        self
+    }
+}
+
+/// A [`HashMap`] with [`Hashed`] keys, which uses the precomputed hash rather than
+/// hashing again, and can be queried with a `Hashed<&Q>` for any `K: Borrow<Q>`.
+///
+/// ```
+/// use gazebo::hash::Hashed;
+/// use gazebo::hash::HashedMap;
+///
+/// let mut map = HashedMap::new();
+/// map.insert(Hashed::new("hello".to_owned()), 1);
+/// assert_eq!(map.get(Hashed::new("hello")), Some(&1));
+/// assert_eq!(map.get(Hashed::new("world")), None);
+/// ```
+#[cfg(feature = "std")]
+pub struct HashedMap<K, V, S = DefaultBuildHasher, H = u64>(
+    HashMap<Hashed<K, S, H>, V, BuildIdentityHasher>,
+);
+
+#[cfg(feature = "std")]
+impl<K, V, S, H> HashedMap<K, V, S, H> {
+    /// An empty map.
+    pub fn new() -> Self {
+        HashedMap(HashMap::default())
+    }
+
+    /// An empty map with space for at least `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        HashedMap(HashMap::with_capacity_and_hasher(
+            capacity,
+            BuildIdentityHasher::default(),
+        ))
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Does the map have no entries.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Remove all the entries.
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    /// Iterate over the entries, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Hashed<K, S, H>, &V)> {
+        self.0.iter()
+    }
+
+    /// Iterate over the keys, in arbitrary order.
+    pub fn keys(&self) -> impl Iterator<Item = &Hashed<K, S, H>> {
+        self.0.keys()
+    }
+
+    /// Iterate over the values, in arbitrary order.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.0.values()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Eq, V, S, H: HashWidth> HashedMap<K, V, S, H> {
+    /// Insert a key and value, returning the previous value for the key.
+    pub fn insert(&mut self, key: Hashed<K, S, H>, value: V) -> Option<V> {
+        self.0.insert(key, value)
+    }
+
+    /// Get the value for a key.
+    pub fn get<Q: ?Sized + Eq>(&self, key: Hashed<&Q, S, H>) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.0.get(&key as &dyn HashedKey<Q, H>)
+    }
+
+    /// Get the value for a key mutably.
+    pub fn get_mut<Q: ?Sized + Eq>(&mut self, key: Hashed<&Q, S, H>) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        self.0.get_mut(&key as &dyn HashedKey<Q, H>)
+    }
+
+    /// Does the map contain the key.
+    pub fn contains_key<Q: ?Sized + Eq>(&self, key: Hashed<&Q, S, H>) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.0.contains_key(&key as &dyn HashedKey<Q, H>)
+    }
+
+    /// Remove a key, returning its value.
+    pub fn remove<Q: ?Sized + Eq>(&mut self, key: Hashed<&Q, S, H>) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.0.remove(&key as &dyn HashedKey<Q, H>)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, H> Default for HashedMap<K, V, S, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Clone, V: Clone, S, H: HashWidth> Clone for HashedMap<K, V, S, H> {
+    fn clone(&self) -> Self {
+        HashedMap(self.0.clone())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Debug, V: Debug, S, H: HashWidth> Debug for HashedMap<K, V, S, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.iter()).finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Eq, V, S, H: HashWidth> FromIterator<(Hashed<K, S, H>, V)> for HashedMap<K, V, S, H> {
+    fn from_iter<I: IntoIterator<Item = (Hashed<K, S, H>, V)>>(iter: I) -> Self {
+        HashedMap(iter.into_iter().collect())
+    }
+}
+
+/// A [`HashSet`] of [`Hashed`] values, which uses the precomputed hash rather than
+/// hashing again, and can be queried with a `Hashed<&Q>` for any `K: Borrow<Q>`.
+///
+/// ```
+/// use gazebo::hash::Hashed;
+/// use gazebo::hash::HashedSet;
+///
+/// let set: HashedSet<String> = ["a", "b"]
+///     .iter()
+///     .map(|x| Hashed::new(x.to_string()))
+///     .collect();
+/// assert!(set.contains(Hashed::new("a")));
+/// assert!(!set.contains(Hashed::new("c")));
+/// ```
+#[cfg(feature = "std")]
+pub struct HashedSet<K, S = DefaultBuildHasher, H = u64>(
+    HashSet<Hashed<K, S, H>, BuildIdentityHasher>,
+);
+
+#[cfg(feature = "std")]
+impl<K, S, H> HashedSet<K, S, H> {
+    /// An empty set.
+    pub fn new() -> Self {
+        HashedSet(HashSet::default())
+    }
+
+    /// An empty set with space for at least `capacity` values.
+    pub fn with_capacity(capacity: usize) -> Self {
+        HashedSet(HashSet::with_capacity_and_hasher(
+            capacity,
+            BuildIdentityHasher::default(),
+        ))
+    }
+
+    /// The number of values.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Does the set have no values.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Remove all the values.
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    /// Iterate over the values, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = &Hashed<K, S, H>> {
+        self.0.iter()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Eq, S, H: HashWidth> HashedSet<K, S, H> {
+    /// Add a value, returning `false` if it was already present.
+    pub fn insert(&mut self, value: Hashed<K, S, H>) -> bool {
+        self.0.insert(value)
+    }
+
+    /// Does the set contain the value.
+    pub fn contains<Q: ?Sized + Eq>(&self, value: Hashed<&Q, S, H>) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.0.contains(&value as &dyn HashedKey<Q, H>)
+    }
+
+    /// Get the stored value equal to the given one.
+    pub fn get<Q: ?Sized + Eq>(&self, value: Hashed<&Q, S, H>) -> Option<&Hashed<K, S, H>>
+    where
+        K: Borrow<Q>,
+    {
+        self.0.get(&value as &dyn HashedKey<Q, H>)
+    }
+
+    /// Remove a value, returning `true` if it was present.
+    pub fn remove<Q: ?Sized + Eq>(&mut self, value: Hashed<&Q, S, H>) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.0.remove(&value as &dyn HashedKey<Q, H>)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, S, H> Default for HashedSet<K, S, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Clone, S, H: HashWidth> Clone for HashedSet<K, S, H> {
+    fn clone(&self) -> Self {
+        HashedSet(self.0.clone())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Debug, S, H: HashWidth> Debug for HashedSet<K, S, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.iter()).finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Eq, S, H: HashWidth> FromIterator<Hashed<K, S, H>> for HashedSet<K, S, H> {
+    fn from_iter<I: IntoIterator<Item = Hashed<K, S, H>>>(iter: I) -> Self {
+        HashedSet(iter.into_iter().collect())
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+    use std::mem;
+
     use super::*;
 
     #[test]
@@ -95,4 +633,148 @@ mod tests {
         assert_eq!(v1, v2);
         assert_ne!(v1, v3);
     }
+
+    #[test]
+    fn test_hashed_with_hasher() {
+        let v1 = Hashed::new_with_hasher("test", FxHasher::default());
+        let v2 = Hashed::new("test");
+        assert_eq!(v1, v2);
+    }
+
+    #[test]
+    fn test_hashed_fx() {
+        let build = FxBuildHasher::default();
+        let v1: Hashed<_, FxBuildHasher> = Hashed::new_with_build_hasher("test", &build);
+        let v2 = Hashed::new_with_hasher("test", FxHasher::default());
+        let v3: Hashed32<_, FxBuildHasher> = Hashed::new_with_build_hasher("test", &build);
+        assert_eq!(v1, v2);
+        assert_eq!(v1.hash, build.hash_one("test"));
+        assert_eq!(v3.hash, u32::from_u64(v1.hash));
+        assert_eq!(mem::size_of::<Hashed32<u32, FxBuildHasher>>(), 8);
+        assert_eq!(mem::size_of::<Hashed<u32>>(), 16);
+
+        let set: HashSet<_> = ["a", "b", "a"]
+            .iter()
+            .map(|x| Hashed32::<_, FxBuildHasher>::new_with_build_hasher(x, &build))
+            .collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_fx_hasher() {
+        let hash = |x: &[u8]| {
+            let mut h = FxHasher::default();
+            h.write(x);
+            h.finish()
+        };
+        // Every length of tail is handled, and distinguished.
+        let xs: Vec<u8> = (1..=16).collect();
+        let hashes: HashSet<_> = (0..=xs.len()).map(|i| hash(&xs[..i])).collect();
+        assert_eq!(hashes.len(), xs.len() + 1);
+        assert_ne!(hash(b"ab"), hash(b"ba"));
+    }
+
+    #[test]
+    fn test_fx_hasher_shifted_keys() {
+        // Keys which only differ in their high bits, like aligned addresses, must still
+        // spread over the low bits.
+        let build = FxBuildHasher::default();
+        let low_bits: HashSet<u64> = (0..1024u64)
+            .map(|i| build.hash_one(i << 20) & 1023)
+            .collect();
+        assert!(low_bits.len() > 600, "{}", low_bits.len());
+        let low_bits: HashSet<u64> = (0..1024u64)
+            .map(|i| Hashed::new(i << 32).hash & 1023)
+            .collect();
+        assert!(low_bits.len() > 600, "{}", low_bits.len());
+    }
+
+    #[test]
+    fn test_hashed_stable() {
+        let x = Hashed::new_stable("hello".to_owned());
+        assert_eq!(x.hash, StableHasher::hash_one("hello"));
+        assert_eq!(x, Hashed::new_stable("hello".to_owned()));
+        assert_ne!(x, Hashed::new_stable("world".to_owned()));
+        let mut map = HashedMap::new();
+        map.insert(x, 1);
+        assert_eq!(map.get(Hashed::new_stable("hello")), Some(&1));
+    }
+
+    #[test]
+    fn test_identity_hasher() {
+        let build = BuildIdentityHasher::default();
+        let x = Hashed::new("test");
+        assert_eq!(build.hash_one(x), x.hash);
+        let y: Hashed32<_> = Hashed::new_with_build_hasher("test", &FxBuildHasher::default());
+        assert_eq!(build.hash_one(y) as u32, y.hash);
+        assert_eq!((build.hash_one(y) >> 32) as u32, y.hash);
+    }
+
+    #[test]
+    fn test_hashed_map_shifted_keys() {
+        // The stored hash is used as is, so its low bits must be mixed for the buckets.
+        fn hashed32<T: Hash>(x: T) -> Hashed32<T> {
+            Hashed::new_with_build_hasher(x, &DefaultBuildHasher::default())
+        }
+
+        let build = BuildIdentityHasher::default();
+        let low_bits: HashSet<u64> = (0..1024u64)
+            .map(|i| build.hash_one(Hashed::new(i << 20)) & 1023)
+            .collect();
+        assert!(low_bits.len() > 600, "{}", low_bits.len());
+        let low_bits: HashSet<u64> = (0..1024u64)
+            .map(|i| build.hash_one(hashed32(i << 20)) & 1023)
+            .collect();
+        assert!(low_bits.len() > 600, "{}", low_bits.len());
+
+        let mut map = HashedMap::new();
+        let mut set = HashedSet::new();
+        for i in 0..20_000u64 {
+            map.insert(Hashed::new(i << 20), i);
+            set.insert(hashed32(i << 20));
+        }
+        for i in 0..20_000u64 {
+            assert_eq!(map.get(Hashed::new(&(i << 20))), Some(&i));
+            assert!(set.contains(hashed32(&(i << 20))));
+        }
+    }
+
+    #[test]
+    fn test_hashed_map() {
+        let mut map = HashedMap::with_capacity(2);
+        assert!(map.is_empty());
+        assert_eq!(map.insert(Hashed::new("a".to_owned()), 1), None);
+        assert_eq!(map.insert(Hashed::new("b".to_owned()), 2), None);
+        assert_eq!(map.insert(Hashed::new("a".to_owned()), 3), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(Hashed::new("a")), Some(&3));
+        *map.get_mut(Hashed::new("b")).unwrap() += 10;
+        assert_eq!(map.get(Hashed::new(&"b".to_owned())), Some(&12));
+        assert!(!map.contains_key(Hashed::new("c")));
+        assert_eq!(map.remove(Hashed::new("a")), Some(3));
+        assert_eq!(format!("{:?}", map.values().collect::<Vec<_>>()), "[12]");
+
+        let map: HashedMap<u32, &str, FxBuildHasher, u32> = [(1, "one"), (2, "two")]
+            .into_iter()
+            .map(|(k, v)| {
+                (
+                    Hashed::new_with_build_hasher(k, &FxBuildHasher::default()),
+                    v,
+                )
+            })
+            .collect();
+        let key = Hashed::new_with_build_hasher(&2, &FxBuildHasher::default());
+        assert_eq!(map.get(key), Some(&"two"));
+    }
+
+    #[test]
+    fn test_hashed_set() {
+        let mut set = HashedSet::new();
+        assert!(set.insert(Hashed::new(vec![1, 2])));
+        assert!(!set.insert(Hashed::new(vec![1, 2])));
+        assert!(set.contains(Hashed::new(&[1, 2][..])));
+        assert_eq!(set.get(Hashed::new(&[1, 2][..])).unwrap().as_ref(), &[1, 2]);
+        assert!(set.remove(Hashed::new(&[1, 2][..])));
+        assert!(set.is_empty());
+    }
 }
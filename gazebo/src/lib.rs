@@ -7,19 +7,31 @@
  * of this source tree.
  */
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(feature = "str_pattern_extensions", feature(pattern))]
 #![cfg_attr(feature = "str_pattern_extensions", feature(associated_type_bounds))]
 
 //! A collection of well-tested primitives that have been useful. Most modules stand alone.
+//!
+//! With the default `std` feature disabled the crate is `no_std` (but requires `alloc`),
+//! and the modules which depend on the operating system, such as `file`, are unavailable.
+
+extern crate alloc;
 
 pub mod cast;
 pub mod cell;
 pub mod cmp;
 pub(crate) mod ext;
+#[cfg(feature = "std")]
 pub mod file;
 pub mod hash;
 pub mod phantom;
 pub mod prelude;
+pub mod small_map;
+pub mod stable_hash;
+#[cfg(feature = "std")]
+pub mod sync;
+pub mod tagged;
 pub mod types;
 pub mod variants;
 
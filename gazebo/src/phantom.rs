@@ -8,72 +8,230 @@
  */
 
 //! Additional [`PhantomData`] related types.
+//!
+//! Each marker fixes one property, either the variance of a type or lifetime, or an
+//! auto trait, and otherwise behaves like [`PhantomData`]: it is zero-sized, all values
+//! are equal, and it implements [`Dupe`], [`Default`], [`Eq`], [`Ord`] and [`Hash`]
+//! whatever its arguments. See [variance on the Nomicon](https://doc.rust-lang.org/nomicon/subtyping.html#variance)
+//! for an explanation of the terms.
+//!
+//! Unlike `PhantomData<T>`, [`PhantomDataCovariant`] and [`PhantomDataContravariant`]
+//! don't own a `T`, so don't affect drop checking, and like [`PhantomInvariantLifetime`]
+//! are always [`Send`] and [`Sync`].
 
-use std::cell::Cell;
-use std::fmt;
-use std::fmt::Debug;
-use std::hash::Hash;
-use std::hash::Hasher;
-use std::marker::PhantomData;
+use core::cell::Cell;
+use core::fmt;
+use core::fmt::Debug;
+use core::hash::Hash;
+use core::hash::Hasher;
+use core::marker::PhantomData;
 
 use dupe::Dupe;
 
 /// A type like [`PhantomData`], but where the contained `T` is invariant
 /// in both lifetimes and types. See [variance on the Nomicon](https://doc.rust-lang.org/nomicon/subtyping.html#variance) for an
 /// explanation of these terms.
+///
+/// Neither a longer nor a shorter lifetime can be substituted:
+///
+/// ```compile_fail
+/// use gazebo::phantom::PhantomDataInvariant;
+///
+/// fn shorten<'a>(x: PhantomDataInvariant<&'static ()>) -> PhantomDataInvariant<&'a ()> {
+///     x
+/// }
+/// ```
 pub struct PhantomDataInvariant<T: ?Sized>(PhantomData<Cell<T>>);
 
-impl<T: ?Sized> PhantomDataInvariant<T> {
-    #[inline]
-    pub const fn new() -> Self {
-        PhantomDataInvariant(PhantomData)
-    }
-}
+/// A type like [`PhantomData`], where `T` is covariant, so a `PhantomDataCovariant<&'static
+/// ()>` can be used as a `PhantomDataCovariant<&'a ()>`.
+///
+/// ```
+/// use gazebo::phantom::PhantomDataCovariant;
+///
+/// fn shorten<'a>(x: PhantomDataCovariant<&'static ()>) -> PhantomDataCovariant<&'a ()> {
+///     x
+/// }
+/// ```
+///
+/// But not the other way round:
+///
+/// ```compile_fail
+/// use gazebo::phantom::PhantomDataCovariant;
+///
+/// fn lengthen<'a>(x: PhantomDataCovariant<&'a ()>) -> PhantomDataCovariant<&'static ()> {
+///     x
+/// }
+/// ```
+pub struct PhantomDataCovariant<T: ?Sized>(PhantomData<fn() -> PhantomData<T>>);
 
-impl<T: ?Sized> Debug for PhantomDataInvariant<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
-        f.pad("PhantomDataInvariant")
-    }
-}
+/// A type like [`PhantomData`], but where `T` is contravariant, so a
+/// `PhantomDataContravariant<&'a ()>` can be used as a
+/// `PhantomDataContravariant<&'static ()>`, as with a function taking a `T`.
+///
+/// ```
+/// use gazebo::phantom::PhantomDataContravariant;
+///
+/// fn lengthen<'a>(x: PhantomDataContravariant<&'a ()>) -> PhantomDataContravariant<&'static ()> {
+///     x
+/// }
+/// ```
+///
+/// But not the other way round:
+///
+/// ```compile_fail
+/// use gazebo::phantom::PhantomDataContravariant;
+///
+/// fn shorten<'a>(x: PhantomDataContravariant<&'static ()>) -> PhantomDataContravariant<&'a ()> {
+///     x
+/// }
+/// ```
+pub struct PhantomDataContravariant<T: ?Sized>(PhantomData<fn(PhantomData<T>)>);
 
-impl<T: ?Sized> Default for PhantomDataInvariant<T> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+/// A marker for a lifetime `'a` which is invariant, e.g. for a brand which must not be
+/// unified with any other lifetime.
+///
+/// ```compile_fail
+/// use gazebo::phantom::PhantomInvariantLifetime;
+///
+/// fn shorten<'a>(x: PhantomInvariantLifetime<'static>) -> PhantomInvariantLifetime<'a> {
+///     x
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use gazebo::phantom::PhantomInvariantLifetime;
+///
+/// fn lengthen<'a>(x: PhantomInvariantLifetime<'a>) -> PhantomInvariantLifetime<'static> {
+///     x
+/// }
+/// ```
+pub struct PhantomInvariantLifetime<'a>(PhantomData<fn(&'a ()) -> &'a ()>);
 
-impl<T: ?Sized> Clone for PhantomDataInvariant<T> {
-    fn clone(&self) -> Self {
-        *self
-    }
-}
+/// A marker which makes a type not [`Send`], while leaving it [`Sync`].
+///
+/// ```
+/// fn require_sync<T: Sync>() {}
+/// require_sync::<gazebo::phantom::PhantomNotSend>();
+/// ```
+///
+/// ```compile_fail,E0277
+/// fn require_send<T: Send>() {}
+/// require_send::<gazebo::phantom::PhantomNotSend>();
+/// ```
+pub struct PhantomNotSend(PhantomData<*mut ()>);
 
-impl<T: ?Sized> Copy for PhantomDataInvariant<T> {}
+// SAFETY: there is no data, so sharing references is harmless. Only `Send` is removed.
+unsafe impl Sync for PhantomNotSend {}
 
-impl<T: ?Sized> Dupe for PhantomDataInvariant<T> {}
+/// A marker which makes a type not [`Sync`], while leaving it [`Send`].
+///
+/// ```
+/// fn require_send<T: Send>() {}
+/// require_send::<gazebo::phantom::PhantomNotSync>();
+/// ```
+///
+/// ```compile_fail,E0277
+/// fn require_sync<T: Sync>() {}
+/// require_sync::<gazebo::phantom::PhantomNotSync>();
+/// ```
+pub struct PhantomNotSync(PhantomData<Cell<()>>);
 
-impl<T: ?Sized> Eq for PhantomDataInvariant<T> {}
+/// The constructor and trait implementations shared by every marker.
+macro_rules! impl_phantom {
+    ([$($generics:tt)*] $ty:ty, $name:literal) => {
+        impl<$($generics)*> $ty {
+            #[inline]
+            pub const fn new() -> Self {
+                Self(PhantomData)
+            }
+        }
 
-impl<T: ?Sized> Hash for PhantomDataInvariant<T> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.hash(state)
-    }
-}
+        impl<$($generics)*> Debug for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.pad($name)
+            }
+        }
 
-impl<T: ?Sized> Ord for PhantomDataInvariant<T> {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.cmp(&other.0)
-    }
-}
+        impl<$($generics)*> Default for $ty {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
 
-impl<T: ?Sized> PartialEq<PhantomDataInvariant<T>> for PhantomDataInvariant<T> {
-    fn eq(&self, other: &PhantomDataInvariant<T>) -> bool {
-        self.0.eq(&other.0)
-    }
+        impl<$($generics)*> Clone for $ty {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<$($generics)*> Copy for $ty {}
+
+        impl<$($generics)*> Dupe for $ty {}
+
+        impl<$($generics)*> Eq for $ty {}
+
+        impl<$($generics)*> Hash for $ty {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state)
+            }
+        }
+
+        impl<$($generics)*> Ord for $ty {
+            fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        impl<$($generics)*> PartialEq for $ty {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.eq(&other.0)
+            }
+        }
+
+        impl<$($generics)*> PartialOrd for $ty {
+            fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+    };
 }
 
-impl<T: ?Sized> PartialOrd<PhantomDataInvariant<T>> for PhantomDataInvariant<T> {
-    fn partial_cmp(&self, other: &PhantomDataInvariant<T>) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl_phantom!([T: ?Sized] PhantomDataInvariant<T>, "PhantomDataInvariant");
+impl_phantom!([T: ?Sized] PhantomDataCovariant<T>, "PhantomDataCovariant");
+impl_phantom!([T: ?Sized] PhantomDataContravariant<T>, "PhantomDataContravariant");
+impl_phantom!(['a] PhantomInvariantLifetime<'a>, "PhantomInvariantLifetime");
+impl_phantom!([] PhantomNotSend, "PhantomNotSend");
+impl_phantom!([] PhantomNotSync, "PhantomNotSync");
+
+#[cfg(test)]
+mod tests {
+    use core::mem;
+
+    use super::*;
+
+    fn require_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_markers() {
+        assert_eq!(mem::size_of::<PhantomDataCovariant<str>>(), 0);
+        assert_eq!(mem::size_of::<PhantomNotSend>(), 0);
+        assert_eq!(
+            PhantomDataContravariant::<u8>::new(),
+            PhantomDataContravariant::default()
+        );
+        assert_eq!(
+            format!("{:?}", PhantomInvariantLifetime::new()),
+            "PhantomInvariantLifetime"
+        );
+        assert_eq!(
+            format!("{:?}", PhantomNotSync::new().dupe()),
+            "PhantomNotSync"
+        );
+
+        // The variance markers don't pick up auto traits from `T`.
+        require_send_sync::<PhantomDataCovariant<*mut u8>>();
+        require_send_sync::<PhantomDataContravariant<*mut u8>>();
+        require_send_sync::<PhantomInvariantLifetime<'static>>();
     }
 }
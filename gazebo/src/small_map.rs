@@ -0,0 +1,795 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A map which preserves insertion order, optimised for a small number of entries.
+//!
+//! Keys are stored as [`Hashed`], so each key is hashed once, when it is inserted.
+//! With few entries a lookup scans the entries comparing hashes, and once the map
+//! grows an index is built over them.
+//!
+//! ```
+//! use gazebo::small_map::SmallMap;
+//!
+//! let mut map = SmallMap::new();
+//! map.insert("b".to_owned(), 2);
+//! map.insert("a".to_owned(), 1);
+//! *map.entry("b".to_owned()).or_insert(0) += 10;
+//! assert_eq!(map.get("b"), Some(&12));
+//! assert_eq!(map.keys().collect::<Vec<_>>(), ["b", "a"]);
+//! assert_eq!(map.to_string(), "{b: 12, a: 1}");
+//! ```
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::fmt::Debug;
+#[cfg(feature = "std")]
+use core::fmt::Display;
+use core::hash::Hash;
+use core::iter;
+use core::mem;
+use core::slice;
+
+#[cfg(feature = "std")]
+use display_container::fmt_keyed_container;
+
+use crate::hash::Hashed;
+
+/// Maps with more entries than this have an index, rather than scanning the entries.
+const NO_INDEX_THRESHOLD: usize = 16;
+
+/// An open addressing table of positions in the entries, probed linearly.
+#[derive(Clone)]
+struct Index {
+    /// Positions, or [`Index::EMPTY`]. The length is a power of two, and at least twice
+    /// the number of entries, so there is always an empty slot to end a probe.
+    slots: Box<[usize]>,
+}
+
+impl Index {
+    const EMPTY: usize = usize::MAX;
+    /// `2^64` divided by the golden ratio, for Fibonacci hashing.
+    const MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+    fn new<K, V>(entries: &[(Hashed<K>, V)]) -> Self {
+        let mut index = Index {
+            slots: vec![Self::EMPTY; (entries.len() * 2).next_power_of_two()].into_boxed_slice(),
+        };
+        for (pos, (k, _)) in entries.iter().enumerate() {
+            index.insert(k.hash_value(), pos);
+        }
+        index
+    }
+
+    fn has_room_for(&self, len: usize) -> bool {
+        len * 2 <= self.slots.len()
+    }
+
+    /// Multiply and take the top bits, so every bit of the hash affects the slot, and
+    /// hashes which only differ in some of their bits don't pile up in one place.
+    fn first_slot(&self, hash: u64) -> usize {
+        let bits = self.slots.len().trailing_zeros();
+        (hash.wrapping_mul(Self::MULTIPLIER) >> (u64::BITS - bits)) as usize
+    }
+
+    fn next_slot(&self, slot: usize) -> usize {
+        (slot + 1) & (self.slots.len() - 1)
+    }
+
+    fn insert(&mut self, hash: u64, pos: usize) {
+        let mut slot = self.first_slot(hash);
+        while self.slots[slot] != Self::EMPTY {
+            slot = self.next_slot(slot);
+        }
+        self.slots[slot] = pos;
+    }
+
+    /// Remove `pos`, whose key has `hash`, given the hashes of the other positions. Later
+    /// members of the probe sequence are shifted back to fill the hole, so lookups don't
+    /// need tombstones.
+    fn remove(&mut self, hash: u64, pos: usize, hash_at: impl Fn(usize) -> u64) {
+        let mask = self.slots.len() - 1;
+        let mut hole = self.first_slot(hash);
+        while self.slots[hole] != pos {
+            hole = self.next_slot(hole);
+        }
+        let mut slot = self.next_slot(hole);
+        while self.slots[slot] != Self::EMPTY {
+            let ideal = self.first_slot(hash_at(self.slots[slot]));
+            // Move the entry back if the hole is between its ideal slot and where it is.
+            if slot.wrapping_sub(ideal) & mask >= slot.wrapping_sub(hole) & mask {
+                self.slots[hole] = self.slots[slot];
+                hole = slot;
+            }
+            slot = self.next_slot(slot);
+        }
+        self.slots[hole] = Self::EMPTY;
+    }
+
+    /// Account for the entry at `pos` being removed from the entries.
+    fn shift_down(&mut self, pos: usize) {
+        for slot in self.slots.iter_mut() {
+            if *slot != Self::EMPTY && *slot > pos {
+                *slot -= 1;
+            }
+        }
+    }
+
+    fn find(&self, hash: u64, mut eq: impl FnMut(usize) -> bool) -> Option<usize> {
+        let mut slot = self.first_slot(hash);
+        loop {
+            match self.slots[slot] {
+                Self::EMPTY => return None,
+                pos if eq(pos) => return Some(pos),
+                _ => slot = self.next_slot(slot),
+            }
+        }
+    }
+}
+
+/// A map which iterates in insertion order, and stores its keys as [`Hashed`].
+///
+/// Equality ignores the order of the entries.
+#[derive(Clone)]
+pub struct SmallMap<K, V> {
+    entries: Vec<(Hashed<K>, V)>,
+    /// Present when there are more than [`NO_INDEX_THRESHOLD`] entries.
+    index: Option<Index>,
+}
+
+impl<K, V> SmallMap<K, V> {
+    /// An empty map.
+    pub const fn new() -> Self {
+        SmallMap {
+            entries: Vec::new(),
+            index: None,
+        }
+    }
+
+    /// An empty map with space for at least `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        SmallMap {
+            entries: Vec::with_capacity(capacity),
+            index: None,
+        }
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Does the map have no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove all the entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index = None;
+    }
+
+    /// Iterate over the entries, in insertion order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.entries.iter().map(|(k, v)| (k.as_ref(), v))
+    }
+
+    /// Iterate over the entries mutably, in insertion order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.entries.iter_mut().map(|(k, v)| (Hashed::as_ref(k), v))
+    }
+
+    /// Iterate over the entries with their hashed keys, in insertion order.
+    pub fn iter_hashed(&self) -> impl Iterator<Item = (Hashed<&K>, &V)> {
+        self.entries.iter().map(|(k, v)| (k.by_ref(), v))
+    }
+
+    /// Iterate over the keys, in insertion order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|(k, _)| k.as_ref())
+    }
+
+    /// Iterate over the values, in insertion order.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    /// Iterate over the values mutably, in insertion order.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.entries.iter_mut().map(|(_, v)| v)
+    }
+
+    /// The entry at position `index` in insertion order.
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get(index).map(|(k, v)| (k.as_ref(), v))
+    }
+
+    /// The entry at position `index` in insertion order, with the value mutable.
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        self.entries
+            .get_mut(index)
+            .map(|(k, v)| (Hashed::as_ref(k), v))
+    }
+
+    /// Sort the entries by key. Subsequent insertions are still added at the end.
+    pub fn sort_keys(&mut self)
+    where
+        K: Ord,
+    {
+        self.entries
+            .sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+        self.rebuild_index();
+    }
+
+    fn rebuild_index(&mut self) {
+        self.index = if self.entries.len() > NO_INDEX_THRESHOLD {
+            Some(Index::new(&self.entries))
+        } else {
+            None
+        };
+    }
+
+    /// Add an entry known not to be present, returning its position.
+    fn push(&mut self, key: Hashed<K>, value: V) -> usize {
+        let pos = self.entries.len();
+        let hash = key.hash_value();
+        self.entries.push((key, value));
+        match &mut self.index {
+            Some(index) if index.has_room_for(self.entries.len()) => index.insert(hash, pos),
+            _ => self.rebuild_index(),
+        }
+        pos
+    }
+
+    fn remove_at(&mut self, pos: usize) -> (Hashed<K>, V) {
+        if self.entries.len() <= NO_INDEX_THRESHOLD + 1 {
+            self.index = None;
+        } else if let Some(index) = &mut self.index {
+            let entries = &self.entries;
+            index.remove(entries[pos].0.hash_value(), pos, |p| {
+                entries[p].0.hash_value()
+            });
+            if pos + 1 != entries.len() {
+                index.shift_down(pos);
+            }
+        }
+        self.entries.remove(pos)
+    }
+
+    /// The position of a key, in insertion order.
+    pub fn get_index_of_hashed<Q: ?Sized + Eq>(&self, key: Hashed<&Q>) -> Option<usize>
+    where
+        K: Borrow<Q>,
+    {
+        let hash = key.hash_value();
+        let eq = |pos: usize| {
+            let k = &self.entries[pos].0;
+            k.hash_value() == hash && k.as_ref().borrow() == *key.as_ref()
+        };
+        match &self.index {
+            None => (0..self.entries.len()).find(|pos| eq(*pos)),
+            Some(index) => index.find(hash, eq),
+        }
+    }
+
+    /// The position of a key, in insertion order.
+    pub fn get_index_of<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+    {
+        self.get_index_of_hashed(Hashed::new(key))
+    }
+
+    /// Get the value for a hashed key.
+    pub fn get_hashed<Q: ?Sized + Eq>(&self, key: Hashed<&Q>) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        let pos = self.get_index_of_hashed(key)?;
+        Some(&self.entries[pos].1)
+    }
+
+    /// Get the value for a key.
+    pub fn get<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.get_hashed(Hashed::new(key))
+    }
+
+    /// Get the value for a hashed key mutably.
+    pub fn get_mut_hashed<Q: ?Sized + Eq>(&mut self, key: Hashed<&Q>) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        let pos = self.get_index_of_hashed(key)?;
+        Some(&mut self.entries[pos].1)
+    }
+
+    /// Get the value for a key mutably.
+    pub fn get_mut<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        self.get_mut_hashed(Hashed::new(key))
+    }
+
+    /// Does the map contain the hashed key.
+    pub fn contains_key_hashed<Q: ?Sized + Eq>(&self, key: Hashed<&Q>) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.get_index_of_hashed(key).is_some()
+    }
+
+    /// Does the map contain the key.
+    pub fn contains_key<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.contains_key_hashed(Hashed::new(key))
+    }
+
+    /// Remove a hashed key, returning its value. Later entries keep their relative order.
+    pub fn remove_hashed<Q: ?Sized + Eq>(&mut self, key: Hashed<&Q>) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let pos = self.get_index_of_hashed(key)?;
+        Some(self.remove_at(pos).1)
+    }
+
+    /// Remove a key, returning its value. Later entries keep their relative order.
+    pub fn remove<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.remove_hashed(Hashed::new(key))
+    }
+}
+
+impl<K: Eq, V> SmallMap<K, V> {
+    /// Insert a hashed key and value. If the key was already present its value is
+    /// replaced, keeping its position, and the old value returned.
+    pub fn insert_hashed(&mut self, key: Hashed<K>, value: V) -> Option<V> {
+        match self.get_index_of_hashed(key.by_ref()) {
+            Some(pos) => Some(mem::replace(&mut self.entries[pos].1, value)),
+            None => {
+                self.push(key, value);
+                None
+            }
+        }
+    }
+
+    /// The entry for a hashed key, for in-place manipulation.
+    pub fn entry_hashed(&mut self, key: Hashed<K>) -> Entry<'_, K, V> {
+        match self.get_index_of_hashed(key.by_ref()) {
+            Some(pos) => Entry::Occupied(OccupiedEntry { map: self, pos }),
+            None => Entry::Vacant(VacantEntry { map: self, key }),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> SmallMap<K, V> {
+    /// Insert a key and value. If the key was already present its value is replaced,
+    /// keeping its position, and the old value returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_hashed(Hashed::new(key), value)
+    }
+
+    /// The entry for a key, for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        self.entry_hashed(Hashed::new(key))
+    }
+}
+
+/// An entry in a [`SmallMap`], from [`SmallMap::entry`].
+pub enum Entry<'a, K, V> {
+    /// The key is present.
+    Occupied(OccupiedEntry<'a, K, V>),
+    /// The key is absent.
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// A present entry in a [`SmallMap`].
+pub struct OccupiedEntry<'a, K, V> {
+    map: &'a mut SmallMap<K, V>,
+    pos: usize,
+}
+
+/// An absent entry in a [`SmallMap`].
+pub struct VacantEntry<'a, K, V> {
+    map: &'a mut SmallMap<K, V>,
+    key: Hashed<K>,
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    /// The key of the entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// Insert `default` if absent, then return the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Insert the result of `default` if absent, then return the value.
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Insert [`Default::default`] if absent, then return the value.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Modify the value if present.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    /// The key of the entry.
+    pub fn key(&self) -> &K {
+        self.map.entries[self.pos].0.as_ref()
+    }
+
+    /// The position of the entry, in insertion order.
+    pub fn index(&self) -> usize {
+        self.pos
+    }
+
+    /// The value of the entry.
+    pub fn get(&self) -> &V {
+        &self.map.entries[self.pos].1
+    }
+
+    /// The value of the entry, mutably.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entries[self.pos].1
+    }
+
+    /// The value of the entry, mutably for the lifetime of the map borrow.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entries[self.pos].1
+    }
+
+    /// Replace the value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Remove the entry, returning its value. Later entries keep their relative order.
+    pub fn remove(self) -> V {
+        self.map.remove_at(self.pos).1
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    /// The key of the entry.
+    pub fn key(&self) -> &K {
+        self.key.as_ref()
+    }
+
+    /// Insert a value at the end of the map, returning it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let pos = self.map.push(self.key, value);
+        &mut self.map.entries[pos].1
+    }
+}
+
+impl<K, V> Default for SmallMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for SmallMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Displays as `{k1: v1, k2: v2}`, or one entry per line with `{:#}`.
+#[cfg(feature = "std")]
+impl<K: Display, V: Display> Display for SmallMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_keyed_container(f, "{", "}", ": ", self.iter())
+    }
+}
+
+impl<K: Eq, V: PartialEq> PartialEq for SmallMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter_hashed()
+                .all(|(k, v)| other.get_hashed(k) == Some(v))
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for SmallMap<K, V> {}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for SmallMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = SmallMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Hash + Eq, V> Extend<(K, V)> for SmallMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+/// An iterator over the entries of a [`SmallMap`], from [`SmallMap::iter`].
+pub type Iter<'a, K, V> =
+    iter::Map<slice::Iter<'a, (Hashed<K>, V)>, fn(&'a (Hashed<K>, V)) -> (&'a K, &'a V)>;
+
+/// An iterator over the owned entries of a [`SmallMap`].
+pub type IntoIter<K, V> = iter::Map<vec::IntoIter<(Hashed<K>, V)>, fn((Hashed<K>, V)) -> (K, V)>;
+
+impl<'a, K, V> IntoIterator for &'a SmallMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V> IntoIterator for SmallMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter().map(|(k, v)| (k.into(), v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hasher;
+
+    use super::*;
+
+    fn keys<K: Clone, V>(map: &SmallMap<K, V>) -> Vec<K> {
+        map.keys().cloned().collect()
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map = SmallMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert(3, "c"), None);
+        assert_eq!(map.insert(1, "a"), None);
+        assert_eq!(map.insert(2, "b"), None);
+        assert_eq!(map.insert(1, "A"), Some("a"));
+        assert_eq!(keys(&map), [3, 1, 2]);
+        assert_eq!(map.get_index(1), Some((&1, &"A")));
+        assert_eq!(map.get_index(3), None);
+        assert_eq!(map.get_index_of(&2), Some(2));
+        assert_eq!(map.remove(&3), Some("c"));
+        assert_eq!(map.remove(&3), None);
+        assert_eq!(keys(&map), [1, 2]);
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), [(1, "A"), (2, "b")]);
+    }
+
+    #[test]
+    fn test_borrowed_lookup() {
+        let mut map = SmallMap::new();
+        map.insert("x".to_owned(), 1);
+        assert_eq!(map.get("x"), Some(&1));
+        *map.get_mut("x").unwrap() += 1;
+        assert_eq!(map.get_hashed(Hashed::new("x")), Some(&2));
+        assert!(!map.contains_key("y"));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut map: SmallMap<u32, u32> = (0..100).map(|x| (x, x * 10)).collect();
+        assert!(map.index.is_some());
+        for x in 0..100 {
+            assert_eq!(map.get(&x), Some(&(x * 10)));
+            assert_eq!(map.get_index_of(&x), Some(x as usize));
+        }
+        assert_eq!(map.get(&100), None);
+        for x in (0..100).filter(|x| x % 3 != 0) {
+            assert_eq!(map.remove(&x), Some(x * 10));
+        }
+        assert_eq!(map.len(), 34);
+        assert_eq!(keys(&map), (0..100).step_by(3).collect::<Vec<_>>());
+        for x in (0..100).step_by(3) {
+            assert_eq!(map.get(&x), Some(&(x * 10)));
+        }
+        for x in 0..90 {
+            map.remove(&x);
+        }
+        assert!(map.index.is_none());
+        assert_eq!(keys(&map), [90, 93, 96, 99]);
+        assert_eq!(map.get(&96), Some(&960));
+    }
+
+    /// The inverse of `Index::MULTIPLIER`, so we can pick hashes which land in given slots.
+    fn multiplier_inverse() -> u64 {
+        // Newton's method, each step doubles the number of correct low bits.
+        let mut inv = Index::MULTIPLIER;
+        for _ in 0..5 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(Index::MULTIPLIER.wrapping_mul(inv)));
+        }
+        assert_eq!(inv.wrapping_mul(Index::MULTIPLIER), 1);
+        inv
+    }
+
+    /// How far each entry is from its first slot, in total.
+    fn total_displacement<K, V>(map: &SmallMap<K, V>) -> usize {
+        let index = map.index.as_ref().unwrap();
+        let mask = index.slots.len() - 1;
+        index
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, pos)| **pos != Index::EMPTY)
+            .map(|(slot, pos)| {
+                slot.wrapping_sub(index.first_slot(map.entries[*pos].0.hash_value())) & mask
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_shifted_hashes() {
+        // Hashes which only differ in their high or low bits, using them as is.
+        #[derive(Default)]
+        struct Raw(u64);
+
+        impl Hasher for Raw {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+
+            fn write(&mut self, _bytes: &[u8]) {
+                unreachable!()
+            }
+
+            fn write_u64(&mut self, i: u64) {
+                self.0 = i;
+            }
+        }
+
+        for shift in [0, 20, 44] {
+            let hashed = |x: u64| Hashed::new_with_hasher(x << shift, Raw::default());
+            let mut map = SmallMap::new();
+            for x in 0..4096 {
+                map.insert_hashed(hashed(x), ());
+            }
+            assert!(total_displacement(&map) < 4 * map.len(), "shift {}", shift);
+            for x in 0..4096 {
+                assert_eq!(
+                    map.get_index_of_hashed(hashed(x).by_ref()),
+                    Some(x as usize)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove_colliding() {
+        // Hashes which all start at the last slot of the table, so probes wrap around.
+        #[derive(Default)]
+        struct Collide(u64);
+
+        impl Hasher for Collide {
+            fn finish(&self) -> u64 {
+                (u64::MAX - self.0 % 8).wrapping_mul(multiplier_inverse())
+            }
+
+            fn write(&mut self, bytes: &[u8]) {
+                for b in bytes {
+                    self.0 = self.0 * 31 + *b as u64;
+                }
+            }
+        }
+
+        let hashed = |x: &u32| Hashed::new_with_hasher(*x, Collide::default());
+        let mut map = SmallMap::new();
+        for x in 0..200 {
+            map.insert_hashed(hashed(&x), x * 10);
+        }
+        let mut expect: Vec<u32> = (0..200).collect();
+        // Remove from the front, middle and back, checking everything after each removal.
+        let mut i = 7;
+        while expect.len() > NO_INDEX_THRESHOLD {
+            i = (i * 13 + 5) % expect.len();
+            let x = expect.remove(i);
+            assert_eq!(map.remove_hashed(hashed(&x).by_ref()), Some(x * 10));
+            assert_eq!(map.get_hashed(hashed(&x).by_ref()), None);
+            for (pos, y) in expect.iter().enumerate() {
+                assert_eq!(map.get_index_of_hashed(hashed(y).by_ref()), Some(pos));
+            }
+        }
+        assert!(map.index.is_none());
+        assert_eq!(keys(&map), expect);
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut map = SmallMap::new();
+        for word in "a b a c b a".split(' ') {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            [(&"a", &3), (&"b", &2), (&"c", &1)]
+        );
+
+        map.entry("b").and_modify(|x| *x *= 10).or_default();
+        map.entry("d").and_modify(|x| *x *= 10).or_default();
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), [3, 20, 1, 0]);
+
+        match map.entry("a") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.key(), &"a");
+                assert_eq!(e.index(), 0);
+                assert_eq!(e.insert(4), 3);
+                assert_eq!(e.remove(), 4);
+            }
+            Entry::Vacant(_) => panic!("expected occupied"),
+        }
+        assert_eq!(keys(&map), ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let mut map: SmallMap<i32, ()> = (0..40).rev().map(|x| (x, ())).collect();
+        map.sort_keys();
+        assert_eq!(keys(&map), (0..40).collect::<Vec<_>>());
+        assert!(map.contains_key(&17));
+        map.insert(-1, ());
+        assert_eq!(map.get_index(40), Some((&-1, &())));
+    }
+
+    #[test]
+    fn test_eq_ignores_order() {
+        let a: SmallMap<_, _> = [(1, "a"), (2, "b")].into_iter().collect();
+        let b: SmallMap<_, _> = [(2, "b"), (1, "a")].into_iter().collect();
+        let c: SmallMap<_, _> = [(2, "b"), (1, "c")].into_iter().collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, SmallMap::new());
+    }
+
+    #[test]
+    fn test_display() {
+        let map: SmallMap<_, _> = [("x", 1), ("y", 2)].into_iter().collect();
+        assert_eq!(map.to_string(), "{x: 1, y: 2}");
+        assert_eq!(format!("{:#}", map), "{\n  x: 1,\n  y: 2\n}");
+        assert_eq!(format!("{:?}", map), r#"{"x": 1, "y": 2}"#);
+        assert_eq!(SmallMap::<i32, i32>::new().to_string(), "{}");
+    }
+}
@@ -0,0 +1,538 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Hashes which are the same on every platform, Rust version and run, so can be persisted,
+//! e.g. as cache keys.
+//!
+//! The output of [`Hash`](core::hash::Hash) depends on details such as the width of
+//! `usize`, and the algorithm behind `DefaultHasher` may change between Rust releases,
+//! so neither is suitable for hashes that outlive the process. Instead,
+//! [`StableHash`] feeds a fixed encoding of a value to [`StableHasher`], which is
+//! SipHash-2-4 with both keys zero.
+//!
+//! The encoding is:
+//!
+//! * Integers as their little-endian bytes, with `usize` and `isize` widened to 64 bits.
+//! * `bool` as one byte, `0` or `1`, and `char` as a `u32`.
+//! * Floats as the little-endian bytes of [`f64::to_bits`] (or [`f32::to_bits`]).
+//! * Strings and sequences as their length, as a `u64`, followed by their contents.
+//!   Arrays are encoded the same way as slices.
+//! * `Option` and `Result` as a tag byte (`None` and `Ok` are `0`) followed by any value.
+//! * Tuples and structs as their fields in order. Enums as the index of the variant
+//!   (counting from zero in declaration order) as a `u32`, followed by its fields.
+//! * References, `Box`, `Rc`, `Arc` and [`Hashed`] as the value they point at or contain.
+//! * `()`, [`PhantomData`] and the markers in [`phantom`](crate::phantom) as nothing.
+//!
+//! Changing any of these, or the hashing algorithm, is a breaking change. Golden tests
+//! below pin the outputs.
+//!
+//! ```
+//! use gazebo::stable_hash::StableHash;
+//! use gazebo::stable_hash::StableHasher;
+//!
+//! #[derive(StableHash)]
+//! struct Key {
+//!     name: String,
+//!     version: u32,
+//! }
+//!
+//! let key = Key {
+//!     name: "foo".to_owned(),
+//!     version: 1,
+//! };
+//! assert_eq!(
+//!     StableHasher::hash_one(&key),
+//!     StableHasher::hash_one(&("foo", 1u32))
+//! );
+//! ```
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::collections::BTreeSet;
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::hash::Hashed;
+use crate::phantom::PhantomDataContravariant;
+use crate::phantom::PhantomDataCovariant;
+use crate::phantom::PhantomDataInvariant;
+use crate::phantom::PhantomInvariantLifetime;
+use crate::phantom::PhantomNotSend;
+use crate::phantom::PhantomNotSync;
+
+/// A value which can be hashed by [`StableHasher`] with a result that is the same across
+/// platforms, Rust versions and processes. See the [module documentation](self) for the
+/// encoding which implementations must follow.
+///
+/// Usually derived with `#[derive(StableHash)]`, which hashes the fields in order, and for
+/// enums first the index of the variant.
+pub trait StableHash {
+    /// Feed this value into the hasher.
+    fn stable_hash(&self, hasher: &mut StableHasher);
+}
+
+/// Derive [`StableHash`](trait@StableHash), hashing each field in declaration order, and
+/// for enums first the index of the variant as a `u32`. Type parameters are required to
+/// implement [`StableHash`](trait@StableHash).
+pub use gazebo_derive::StableHash;
+
+/// SipHash-2-4 with both keys zero, as used by [`StableHash`].
+#[derive(Debug, Clone)]
+pub struct StableHasher {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    /// Bytes not yet compressed, little-endian, of which there are `length % 8`.
+    tail: u64,
+    length: u64,
+}
+
+impl StableHasher {
+    /// A fresh hasher.
+    pub const fn new() -> Self {
+        StableHasher {
+            v0: 0x736f6d6570736575,
+            v1: 0x646f72616e646f6d,
+            v2: 0x6c7967656e657261,
+            v3: 0x7465646279746573,
+            tail: 0,
+            length: 0,
+        }
+    }
+
+    /// Hash a single value with a fresh hasher.
+    pub fn hash_one<T: StableHash + ?Sized>(x: &T) -> u64 {
+        let mut hasher = StableHasher::new();
+        x.stable_hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[inline]
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13);
+        self.v1 ^= self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16);
+        self.v3 ^= self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21);
+        self.v3 ^= self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17);
+        self.v1 ^= self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    #[inline]
+    fn compress(&mut self, m: u64) {
+        self.v3 ^= m;
+        self.round();
+        self.round();
+        self.v0 ^= m;
+    }
+
+    /// Feed raw bytes into the hasher. Only the concatenation of everything written
+    /// matters, not how it was split into calls.
+    pub fn write(&mut self, mut bytes: &[u8]) {
+        let mut ntail = (self.length % 8) as usize;
+        self.length = self.length.wrapping_add(bytes.len() as u64);
+        if ntail != 0 {
+            while ntail < 8 {
+                let Some((b, rest)) = bytes.split_first() else {
+                    return;
+                };
+                self.tail |= (*b as u64) << (8 * ntail);
+                ntail += 1;
+                bytes = rest;
+            }
+            self.compress(self.tail);
+            self.tail = 0;
+        }
+        while let Some((chunk, rest)) = bytes.split_first_chunk::<8>() {
+            self.compress(u64::from_le_bytes(*chunk));
+            bytes = rest;
+        }
+        for (i, b) in bytes.iter().enumerate() {
+            self.tail |= (*b as u64) << (8 * i);
+        }
+    }
+
+    /// Feed the length of a string or sequence into the hasher, as a `u64`.
+    pub fn write_length(&mut self, len: usize) {
+        self.write(&(len as u64).to_le_bytes());
+    }
+
+    /// The hash of everything written so far.
+    pub fn finish(&self) -> u64 {
+        let mut s = self.clone();
+        s.compress(((self.length & 0xff) << 56) | self.tail);
+        s.v2 ^= 0xff;
+        for _ in 0..4 {
+            s.round();
+        }
+        s.v0 ^ s.v1 ^ s.v2 ^ s.v3
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+macro_rules! impl_stable_hash_int {
+    ($($t:ty)*) => {
+        $(
+            impl StableHash for $t {
+                fn stable_hash(&self, hasher: &mut StableHasher) {
+                    hasher.write(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_stable_hash_int!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128);
+
+impl StableHash for usize {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (*self as u64).stable_hash(hasher)
+    }
+}
+
+impl StableHash for isize {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (*self as i64).stable_hash(hasher)
+    }
+}
+
+impl StableHash for bool {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (*self as u8).stable_hash(hasher)
+    }
+}
+
+impl StableHash for char {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (*self as u32).stable_hash(hasher)
+    }
+}
+
+impl StableHash for f32 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.to_bits().stable_hash(hasher)
+    }
+}
+
+impl StableHash for f64 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.to_bits().stable_hash(hasher)
+    }
+}
+
+impl StableHash for str {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_length(self.len());
+        hasher.write(self.as_bytes());
+    }
+}
+
+impl StableHash for String {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_str().stable_hash(hasher)
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_length(self.len());
+        for x in self {
+            x.stable_hash(hasher);
+        }
+    }
+}
+
+impl<T: StableHash, const N: usize> StableHash for [T; N] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_slice().stable_hash(hasher)
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_slice().stable_hash(hasher)
+    }
+}
+
+impl<T: StableHash> StableHash for VecDeque<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_length(self.len());
+        for x in self {
+            x.stable_hash(hasher);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for BTreeSet<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_length(self.len());
+        for x in self {
+            x.stable_hash(hasher);
+        }
+    }
+}
+
+impl<K: StableHash, V: StableHash> StableHash for BTreeMap<K, V> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_length(self.len());
+        for (k, v) in self {
+            k.stable_hash(hasher);
+            v.stable_hash(hasher);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for Option<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            None => 0u8.stable_hash(hasher),
+            Some(x) => {
+                1u8.stable_hash(hasher);
+                x.stable_hash(hasher);
+            }
+        }
+    }
+}
+
+impl<T: StableHash, E: StableHash> StableHash for Result<T, E> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Ok(x) => {
+                0u8.stable_hash(hasher);
+                x.stable_hash(hasher);
+            }
+            Err(e) => {
+                1u8.stable_hash(hasher);
+                e.stable_hash(hasher);
+            }
+        }
+    }
+}
+
+macro_rules! impl_stable_hash_pointer {
+    ($($t:ty)*) => {
+        $(
+            impl<T: StableHash + ?Sized> StableHash for $t {
+                fn stable_hash(&self, hasher: &mut StableHasher) {
+                    (**self).stable_hash(hasher)
+                }
+            }
+        )*
+    };
+}
+
+impl_stable_hash_pointer!(&T &mut T Box<T> Rc<T> Arc<T>);
+
+macro_rules! impl_stable_hash_tuple {
+    ($($x:ident)*) => {
+        impl<$($x: StableHash),*> StableHash for ($($x,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn stable_hash(&self, hasher: &mut StableHasher) {
+                let ($($x,)*) = self;
+                $($x.stable_hash(hasher);)*
+            }
+        }
+    };
+}
+
+impl_stable_hash_tuple!();
+impl_stable_hash_tuple!(A);
+impl_stable_hash_tuple!(A B);
+impl_stable_hash_tuple!(A B C);
+impl_stable_hash_tuple!(A B C D);
+impl_stable_hash_tuple!(A B C D E);
+impl_stable_hash_tuple!(A B C D E F);
+impl_stable_hash_tuple!(A B C D E F G);
+impl_stable_hash_tuple!(A B C D E F G H);
+
+impl<T: ?Sized> StableHash for PhantomData<T> {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl<T: ?Sized> StableHash for PhantomDataInvariant<T> {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl<T: ?Sized> StableHash for PhantomDataCovariant<T> {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl<T: ?Sized> StableHash for PhantomDataContravariant<T> {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl StableHash for PhantomInvariantLifetime<'_> {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl StableHash for PhantomNotSend {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl StableHash for PhantomNotSync {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl<T: StableHash, S, H> StableHash for Hashed<T, S, H> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_ref().stable_hash(hasher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::ToOwned;
+    use alloc::vec;
+
+    use super::*;
+    #[allow(unused_imports)]
+    use crate as gazebo;
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_siphash() {
+        use core::hash::Hasher;
+        use core::hash::SipHasher;
+
+        let input: Vec<u8> = (0..100).collect();
+        for len in 0..input.len() {
+            let mut expected = SipHasher::new();
+            expected.write(&input[..len]);
+            let mut hasher = StableHasher::new();
+            // Split the writes unevenly, which mustn't matter.
+            let (a, b) = input[..len].split_at(len / 3);
+            hasher.write(a);
+            hasher.write(b);
+            assert_eq!(hasher.finish(), expected.finish(), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_golden() {
+        assert_eq!(StableHasher::hash_one(&()), 0x1e924b9d737700d7);
+        assert_eq!(StableHasher::hash_one(&0u8), 0x8b5a0baa49fbc58d);
+        assert_eq!(StableHasher::hash_one(&42u32), 0x410a4b0236d7f312);
+        assert_eq!(StableHasher::hash_one(&-1i64), 0x8050c18b6ac9d15e);
+        assert_eq!(StableHasher::hash_one(&true), 0x667dd5401e6fd800);
+        assert_eq!(StableHasher::hash_one(&'x'), 0x84f48f3e21cb6fce);
+        assert_eq!(StableHasher::hash_one(&1.5f64), 0xa8f425a4dd330080);
+        assert_eq!(StableHasher::hash_one("hello"), 0xd17f96bd0852ce12);
+        assert_eq!(StableHasher::hash_one(&[1u16, 2, 3]), 0x4226e50fcf202b77);
+        assert_eq!(StableHasher::hash_one(&Some(7u64)), 0x2e29dbec501bd71e);
+        assert_eq!(StableHasher::hash_one(&("a", 1u8)), 0x4cae0ee332bed12d);
+    }
+
+    #[test]
+    fn test_encoding() {
+        // Widths are fixed, whatever the platform.
+        assert_eq!(
+            StableHasher::hash_one(&7usize),
+            StableHasher::hash_one(&7u64)
+        );
+        assert_eq!(
+            StableHasher::hash_one(&-7isize),
+            StableHasher::hash_one(&-7i64)
+        );
+        // Pointers and containers are transparent.
+        assert_eq!(
+            StableHasher::hash_one(&"x".to_owned()),
+            StableHasher::hash_one("x")
+        );
+        assert_eq!(
+            StableHasher::hash_one(&Arc::new(vec![1u8])),
+            StableHasher::hash_one(&[1u8])
+        );
+        assert_eq!(
+            StableHasher::hash_one(&Hashed::new(3u8)),
+            StableHasher::hash_one(&3u8)
+        );
+        assert_eq!(
+            StableHasher::hash_one(&(1u8, PhantomDataInvariant::<u8>::new())),
+            StableHasher::hash_one(&(1u8,))
+        );
+        // Lengths keep adjacent strings apart.
+        assert_ne!(
+            StableHasher::hash_one(&("ab", "c")),
+            StableHasher::hash_one(&("a", "bc"))
+        );
+        assert_ne!(
+            StableHasher::hash_one(&None::<u8>),
+            StableHasher::hash_one(&Some(0u8))
+        );
+    }
+
+    #[test]
+    fn test_derive() {
+        #[derive(StableHash)]
+        struct Named<T> {
+            x: T,
+            y: String,
+        }
+
+        #[derive(StableHash)]
+        struct Unnamed(u8, bool);
+
+        #[derive(StableHash)]
+        struct Unit;
+
+        #[derive(StableHash)]
+        enum Enum {
+            A,
+            B(u8),
+            C { x: u8, y: u8 },
+        }
+
+        let named = Named {
+            x: 1u8,
+            y: "a".to_owned(),
+        };
+        assert_eq!(
+            StableHasher::hash_one(&named),
+            StableHasher::hash_one(&(1u8, "a"))
+        );
+        assert_eq!(
+            StableHasher::hash_one(&Unnamed(1, true)),
+            StableHasher::hash_one(&(1u8, true))
+        );
+        assert_eq!(StableHasher::hash_one(&Unit), StableHasher::hash_one(&()));
+        assert_eq!(
+            StableHasher::hash_one(&Enum::A),
+            StableHasher::hash_one(&0u32)
+        );
+        assert_eq!(
+            StableHasher::hash_one(&Enum::B(5)),
+            StableHasher::hash_one(&(1u32, 5u8))
+        );
+        assert_eq!(
+            StableHasher::hash_one(&Enum::C { x: 1, y: 2 }),
+            StableHasher::hash_one(&(2u32, 1u8, 2u8))
+        );
+        assert_eq!(
+            StableHasher::hash_one(&Enum::C { x: 1, y: 2 }),
+            0xd4c812339fddacb9
+        );
+    }
+}
@@ -0,0 +1,251 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Thread-safe counterparts to the [`ARef`](crate::cell::ARef) mechanism.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::ops::Deref;
+use std::sync::LockResult;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+use std::sync::RwLockReadGuard;
+use std::sync::TryLockError;
+use std::sync::TryLockResult;
+
+#[derive(Debug)]
+enum AGuardImpl<'a, T: ?Sized + 'a> {
+    Ptr(&'a T),
+    Read(RwLockReadGuard<'a, T>),
+    Lock(MutexGuard<'a, T>),
+}
+
+/// A lock guard that might not actually be holding a lock.
+/// Either a `Ptr` (a normal & style reference), a `Read` (from [`RwLock::read`])
+/// or a `Lock` (from [`Mutex::lock`]). The lock, if any, is released on drop.
+#[derive(Debug)]
+pub struct AGuard<'a, T: ?Sized + 'a>(AGuardImpl<'a, T>);
+
+impl<T: ?Sized> Deref for AGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match &self.0 {
+            AGuardImpl::Ptr(p) => p,
+            AGuardImpl::Read(r) => r.deref(),
+            AGuardImpl::Lock(l) => l.deref(),
+        }
+    }
+}
+
+impl<'a, T: ?Sized + 'a> AGuard<'a, T> {
+    /// Create a new [`AGuard`] from a pointer.
+    pub fn new_ptr(x: &'a T) -> Self {
+        AGuard(AGuardImpl::Ptr(x))
+    }
+
+    /// Create a new [`AGuard`] from a [`RwLock`] read guard.
+    pub fn new_read(x: RwLockReadGuard<'a, T>) -> Self {
+        AGuard(AGuardImpl::Read(x))
+    }
+
+    /// Create a new [`AGuard`] from a [`Mutex`] guard.
+    pub fn new_lock(x: MutexGuard<'a, T>) -> Self {
+        AGuard(AGuardImpl::Lock(x))
+    }
+}
+
+impl<T: Display + ?Sized> Display for AGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        AGuard::deref(self).fmt(f)
+    }
+}
+
+impl<T: Hash + ?Sized> Hash for AGuard<'_, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        AGuard::deref(self).hash(state)
+    }
+}
+
+impl<A: PartialEq<B> + ?Sized, B: ?Sized> PartialEq<AGuard<'_, B>> for AGuard<'_, A> {
+    fn eq(&self, other: &AGuard<'_, B>) -> bool {
+        AGuard::deref(self).eq(AGuard::deref(other))
+    }
+}
+
+impl<A: Eq + ?Sized> Eq for AGuard<'_, A> {}
+
+impl<A: PartialOrd<B> + ?Sized, B: ?Sized> PartialOrd<AGuard<'_, B>> for AGuard<'_, A> {
+    fn partial_cmp(&self, other: &AGuard<'_, B>) -> Option<Ordering> {
+        AGuard::deref(self).partial_cmp(AGuard::deref(other))
+    }
+}
+
+impl<A: Ord + ?Sized> Ord for AGuard<'_, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        AGuard::deref(self).cmp(AGuard::deref(other))
+    }
+}
+
+fn map_lock_result<G, R>(x: LockResult<G>, f: impl FnOnce(G) -> R) -> LockResult<R> {
+    match x {
+        Ok(g) => Ok(f(g)),
+        Err(e) => Err(PoisonError::new(f(e.into_inner()))),
+    }
+}
+
+fn map_try_lock_result<G, R>(x: TryLockResult<G>, f: impl FnOnce(G) -> R) -> TryLockResult<R> {
+    match x {
+        Ok(g) => Ok(f(g)),
+        Err(TryLockError::Poisoned(e)) => {
+            Err(TryLockError::Poisoned(PoisonError::new(f(e.into_inner()))))
+        }
+        Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+    }
+}
+
+/// Obtain an [`AGuard`] from either a normal pointer, a [`RwLock`] or a [`Mutex`].
+///
+/// Poisoning is reported the same way as the underlying lock does, so the guard can
+/// still be recovered with [`PoisonError::into_inner`].
+pub trait AsAGuard<T: ?Sized> {
+    /// Get an [`AGuard`] pointing at this type, blocking until the lock is acquired.
+    /// Returns an [`Err`] if `Self` is a lock which has been poisoned.
+    fn as_aguard(this: &Self) -> LockResult<AGuard<'_, T>>;
+    /// Try and get an [`AGuard`] pointing at this type without blocking. Returns an
+    /// [`Err`] if `Self` is a lock which is poisoned or currently held for writing.
+    fn try_as_aguard(this: &Self) -> TryLockResult<AGuard<'_, T>>;
+}
+
+impl<T: ?Sized> AsAGuard<T> for T {
+    fn as_aguard(this: &Self) -> LockResult<AGuard<'_, T>> {
+        Ok(AGuard::new_ptr(this))
+    }
+    fn try_as_aguard(this: &Self) -> TryLockResult<AGuard<'_, T>> {
+        Ok(AGuard::new_ptr(this))
+    }
+}
+
+impl<T: ?Sized> AsAGuard<T> for RwLock<T> {
+    fn as_aguard(this: &Self) -> LockResult<AGuard<'_, T>> {
+        map_lock_result(this.read(), AGuard::new_read)
+    }
+    fn try_as_aguard(this: &Self) -> TryLockResult<AGuard<'_, T>> {
+        map_try_lock_result(this.try_read(), AGuard::new_read)
+    }
+}
+
+impl<T: ?Sized> AsAGuard<T> for Mutex<T> {
+    fn as_aguard(this: &Self) -> LockResult<AGuard<'_, T>> {
+        map_lock_result(this.lock(), AGuard::new_lock)
+    }
+    fn try_as_aguard(this: &Self) -> TryLockResult<AGuard<'_, T>> {
+        map_try_lock_result(this.try_lock(), AGuard::new_lock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    fn get_len(x: &impl AsAGuard<String>) -> usize {
+        AsAGuard::as_aguard(x).unwrap().len()
+    }
+
+    #[test]
+    fn test_as_aguard() {
+        let a = "hello".to_owned();
+        let b = RwLock::new("hello world".to_owned());
+        let c = Mutex::new("hi".to_owned());
+        assert_eq!(get_len(&a), 5);
+        assert_eq!(get_len(&b), 11);
+        assert_eq!(get_len(&c), 2);
+    }
+
+    #[test]
+    fn test_lock_released() {
+        let m = Mutex::new(1);
+        let g: AGuard<i32> = AsAGuard::as_aguard(&m).unwrap();
+        assert!(m.try_lock().is_err());
+        mem::drop(g);
+        assert!(m.try_lock().is_ok());
+
+        let l = RwLock::new(1);
+        let g1: AGuard<i32> = AsAGuard::as_aguard(&l).unwrap();
+        let g2: AGuard<i32> = AsAGuard::try_as_aguard(&l).unwrap();
+        assert_eq!(g1, g2);
+        assert!(l.try_write().is_err());
+        mem::drop((g1, g2));
+        assert!(l.try_write().is_ok());
+    }
+
+    #[test]
+    fn test_try_would_block() {
+        let m = Mutex::new(1);
+        let _g = m.lock().unwrap();
+        assert!(matches!(
+            <Mutex<i32> as AsAGuard<i32>>::try_as_aguard(&m),
+            Err(TryLockError::WouldBlock)
+        ));
+
+        let l = RwLock::new(1);
+        let _w = l.write().unwrap();
+        assert!(matches!(
+            <RwLock<i32> as AsAGuard<i32>>::try_as_aguard(&l),
+            Err(TryLockError::WouldBlock)
+        ));
+    }
+
+    #[test]
+    fn test_poisoned() {
+        let m = Arc::new(Mutex::new(7));
+        let m2 = m.clone();
+        let res = thread::spawn(move || {
+            let _g = m2.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(res.is_err());
+
+        let g: AGuard<i32> = AsAGuard::as_aguard(&*m).unwrap_err().into_inner();
+        assert_eq!(*g, 7);
+        mem::drop(g);
+        let res = <Mutex<i32> as AsAGuard<i32>>::try_as_aguard(&m);
+        match res {
+            Err(TryLockError::Poisoned(e)) => assert_eq!(*e.into_inner(), 7),
+            _ => panic!("expected the lock to be poisoned"),
+        }
+    }
+
+    #[test]
+    fn test_across_threads() {
+        let l = Arc::new(RwLock::new(vec![1, 2, 3]));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let l = l.clone();
+                thread::spawn(move || {
+                    let xs: AGuard<Vec<i32>> = AsAGuard::as_aguard(&*l).unwrap();
+                    xs.iter().sum::<i32>()
+                })
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), 6);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Pointers with a small tag packed into the low bits freed up by alignment.
+//!
+//! A `T` with alignment `2^n` always has the bottom `n` bits of its address set to zero,
+//! so they can store a tag. Both [`TaggedPtr`] and [`TaggedRef`] check at compile time that
+//! `T` is aligned enough for `BITS` bits of tag, and only ever change the address of the
+//! pointer (never casting through `usize`), so they keep the provenance of the original.
+//!
+//! ```
+//! use gazebo::tagged::TaggedRef;
+//!
+//! let x = 42u64;
+//! let t = TaggedRef::<_, 3>::new(&x, 5);
+//! assert_eq!(*t.ptr(), 42);
+//! assert_eq!(t.tag(), 5);
+//! ```
+//!
+//! Asking for more bits than the alignment provides is a compile error:
+//!
+//! ```compile_fail,E0080
+//! let x = 42u16;
+//! let _ = gazebo::tagged::TaggedRef::<_, 2>::new(&x, 0);
+//! ```
+
+use core::fmt;
+use core::fmt::Debug;
+use core::hash::Hash;
+use core::hash::Hasher;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::NonNull;
+
+use dupe::Dupe;
+
+/// A [`NonNull`] pointer to `T` with a `BITS` bit tag stored in its low bits.
+pub struct TaggedPtr<T, const BITS: u32>(NonNull<T>);
+
+impl<T, const BITS: u32> TaggedPtr<T, BITS> {
+    const MASK: usize = (1 << BITS) - 1;
+
+    /// Pack a tag into a pointer. Panics if the tag doesn't fit in `BITS` bits or the pointer
+    /// isn't aligned for `T`.
+    #[inline]
+    pub fn new(ptr: NonNull<T>, tag: usize) -> Self {
+        const {
+            assert!(
+                BITS < usize::BITS && (1 << BITS) <= mem::align_of::<T>(),
+                "TaggedPtr: alignment of T leaves too few bits for the tag"
+            )
+        };
+        assert!(tag <= Self::MASK, "TaggedPtr: tag doesn't fit in BITS bits");
+        assert!(ptr.is_aligned(), "TaggedPtr: pointer isn't aligned");
+        // SAFETY: The tag is only added to the zero bits of a non-null address.
+        TaggedPtr(unsafe { NonNull::new_unchecked(ptr.as_ptr().map_addr(|a| a | tag)) })
+    }
+
+    /// The pointer, without the tag.
+    #[inline]
+    pub fn ptr(self) -> NonNull<T> {
+        // SAFETY: The original pointer was non-null and we only clear the tag bits.
+        unsafe { NonNull::new_unchecked(self.0.as_ptr().map_addr(|a| a & !Self::MASK)) }
+    }
+
+    /// The tag, which is less than `2^BITS`.
+    #[inline]
+    pub fn tag(self) -> usize {
+        self.0.as_ptr().addr() & Self::MASK
+    }
+}
+
+impl<T, const BITS: u32> Clone for TaggedPtr<T, BITS> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, const BITS: u32> Copy for TaggedPtr<T, BITS> {}
+
+impl<T, const BITS: u32> Dupe for TaggedPtr<T, BITS> {}
+
+impl<T, const BITS: u32> PartialEq for TaggedPtr<T, BITS> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T, const BITS: u32> Eq for TaggedPtr<T, BITS> {}
+
+impl<T, const BITS: u32> Hash for TaggedPtr<T, BITS> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<T, const BITS: u32> Debug for TaggedPtr<T, BITS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaggedPtr")
+            .field("ptr", &self.ptr())
+            .field("tag", &self.tag())
+            .finish()
+    }
+}
+
+/// A reference to `T` with a `BITS` bit tag stored in its low bits.
+pub struct TaggedRef<'a, T, const BITS: u32>(TaggedPtr<T, BITS>, PhantomData<&'a T>);
+
+// SAFETY: Behaves like a `&'a T`.
+unsafe impl<T: Sync, const BITS: u32> Send for TaggedRef<'_, T, BITS> {}
+// SAFETY: Behaves like a `&'a T`.
+unsafe impl<T: Sync, const BITS: u32> Sync for TaggedRef<'_, T, BITS> {}
+
+impl<'a, T, const BITS: u32> TaggedRef<'a, T, BITS> {
+    /// Pack a tag into a reference. Panics if the tag doesn't fit in `BITS` bits.
+    #[inline]
+    pub fn new(x: &'a T, tag: usize) -> Self {
+        TaggedRef(TaggedPtr::new(NonNull::from(x), tag), PhantomData)
+    }
+
+    /// The reference, without the tag.
+    #[inline]
+    pub fn ptr(self) -> &'a T {
+        // SAFETY: Came from a `&'a T`.
+        unsafe { self.0.ptr().as_ref() }
+    }
+
+    /// The tag, which is less than `2^BITS`.
+    #[inline]
+    pub fn tag(self) -> usize {
+        self.0.tag()
+    }
+}
+
+impl<T, const BITS: u32> Clone for TaggedRef<'_, T, BITS> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, const BITS: u32> Copy for TaggedRef<'_, T, BITS> {}
+
+impl<T, const BITS: u32> Dupe for TaggedRef<'_, T, BITS> {}
+
+impl<T, const BITS: u32> PartialEq for TaggedRef<'_, T, BITS> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T, const BITS: u32> Eq for TaggedRef<'_, T, BITS> {}
+
+impl<T, const BITS: u32> Hash for TaggedRef<'_, T, BITS> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<T: Debug, const BITS: u32> Debug for TaggedRef<'_, T, BITS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaggedRef")
+            .field("ptr", self.ptr())
+            .field("tag", &self.tag())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tagged_ptr() {
+        let mut x = 7u32;
+        let p = NonNull::from(&mut x);
+        for tag in 0..4 {
+            let t = TaggedPtr::<u32, 2>::new(p, tag);
+            assert_eq!(t.ptr(), p);
+            assert_eq!(t.tag(), tag);
+            assert_eq!(unsafe { *t.ptr().as_ptr() }, 7);
+        }
+        assert_ne!(TaggedPtr::<u32, 2>::new(p, 1), TaggedPtr::new(p, 2));
+        assert_eq!(
+            mem::size_of::<Option<TaggedPtr<u32, 2>>>(),
+            mem::size_of::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_tagged_ref() {
+        let xs = [1u64, 2, 3];
+        let ts: Vec<TaggedRef<u64, 3>> = xs
+            .iter()
+            .enumerate()
+            .map(|(i, x)| TaggedRef::new(x, i + 4))
+            .collect();
+        for (i, t) in ts.iter().enumerate() {
+            assert_eq!(*t.dupe().ptr(), xs[i]);
+            assert_eq!(t.tag(), i + 4);
+        }
+        assert_eq!(format!("{:?}", ts[0]), "TaggedRef { ptr: 1, tag: 4 }");
+    }
+
+    #[test]
+    #[should_panic(expected = "tag doesn't fit")]
+    fn test_tag_too_big() {
+        TaggedRef::<u32, 2>::new(&1, 4);
+    }
+}
@@ -12,6 +12,19 @@
 // If we need more type operations we should probably model them on
 // https://hackage.haskell.org/package/base-4.12.0.0/docs/Data-Type-Equality.html
 
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+
+use dupe::Dupe;
+
+use crate::cast;
+
 /// A trait witnessing that two types are equal.
 ///
 /// For example:
@@ -47,3 +60,220 @@ impl<T> TEq<T> for T {
         self
     }
 }
+
+/// A value witnessing that the types `A` and `B` are equal, modelled on
+/// [`:~:`](https://hackage.haskell.org/package/base-4.12.0.0/docs/Data-Type-Equality.html)
+/// in Haskell. Unlike [`TEq`], which is a constraint, a `Refl` can be stored, passed around
+/// and composed, and used to cast containers of `A` into containers of `B`.
+///
+/// A `Refl` can only be created with [`Refl::new`], when the types are syntactically equal,
+/// or [`Refl::test`], which compares the types at runtime. That lets generic code specialise
+/// for a particular type:
+///
+/// ```
+/// use gazebo::types::Refl;
+///
+/// fn describe<T: 'static>(xs: Vec<T>) -> String {
+///     match Refl::<T, String>::test() {
+///         Some(refl) => refl.cast_vec(xs).join(","),
+///         None => format!("{} values", xs.len()),
+///     }
+/// }
+///
+/// assert_eq!(describe(vec!["a".to_owned(), "b".to_owned()]), "a,b");
+/// assert_eq!(describe(vec![1, 2, 3]), "3 values");
+/// ```
+pub struct Refl<A: ?Sized, B: ?Sized>(PhantomData<(Invariant<A>, Invariant<B>)>);
+
+// Makes `Refl` invariant in both `A` and `B`, so a witness for one lifetime can't be used
+// for another, while still being `Send` and `Sync`.
+type Invariant<T> = fn(&T) -> &T;
+
+impl<A: ?Sized> Refl<A, A> {
+    /// The witness that a type is equal to itself.
+    #[inline]
+    pub const fn new() -> Self {
+        Refl(PhantomData)
+    }
+}
+
+impl<A: ?Sized + 'static, B: ?Sized + 'static> Refl<A, B> {
+    /// A witness that `A` and `B` are equal, if they have the same [`TypeId`].
+    #[inline]
+    pub fn test() -> Option<Self> {
+        if TypeId::of::<A>() == TypeId::of::<B>() {
+            Some(Refl(PhantomData))
+        } else {
+            None
+        }
+    }
+}
+
+impl<A: ?Sized, B: ?Sized> Refl<A, B> {
+    /// Equality is symmetric.
+    #[inline]
+    pub fn symm(self) -> Refl<B, A> {
+        Refl(PhantomData)
+    }
+
+    /// Equality is transitive.
+    #[inline]
+    pub fn trans<C: ?Sized>(self, _other: Refl<B, C>) -> Refl<A, C> {
+        Refl(PhantomData)
+    }
+
+    /// Convert a reference to `A` into a reference to `B`.
+    #[inline]
+    pub fn cast_ref(self, x: &A) -> &B {
+        // SAFETY: `A` and `B` are the same type.
+        unsafe { cast::transmute_checked(x) }
+    }
+
+    /// Convert a mutable reference to `A` into a mutable reference to `B`.
+    #[inline]
+    pub fn cast_mut(self, x: &mut A) -> &mut B {
+        // SAFETY: `A` and `B` are the same type.
+        unsafe { cast::transmute_checked(x) }
+    }
+
+    /// If `A` equals `B`, then `Box<A>` equals `Box<B>`.
+    #[inline]
+    pub fn lift_box(self) -> Refl<Box<A>, Box<B>> {
+        Refl(PhantomData)
+    }
+
+    /// If `A` equals `B`, then `Rc<A>` equals `Rc<B>`.
+    #[inline]
+    pub fn lift_rc(self) -> Refl<Rc<A>, Rc<B>> {
+        Refl(PhantomData)
+    }
+
+    /// If `A` equals `B`, then `Arc<A>` equals `Arc<B>`.
+    #[inline]
+    pub fn lift_arc(self) -> Refl<Arc<A>, Arc<B>> {
+        Refl(PhantomData)
+    }
+
+    /// Convert a `Box<A>` into a `Box<B>`.
+    #[inline]
+    pub fn cast_box(self, x: Box<A>) -> Box<B> {
+        self.lift_box().cast(x)
+    }
+
+    /// Convert an `Rc<A>` into an `Rc<B>`.
+    #[inline]
+    pub fn cast_rc(self, x: Rc<A>) -> Rc<B> {
+        self.lift_rc().cast(x)
+    }
+
+    /// Convert an `Arc<A>` into an `Arc<B>`.
+    #[inline]
+    pub fn cast_arc(self, x: Arc<A>) -> Arc<B> {
+        self.lift_arc().cast(x)
+    }
+}
+
+impl<A, B> Refl<A, B> {
+    /// Convert an `A` into a `B`.
+    #[inline]
+    pub fn cast(self, x: A) -> B {
+        // SAFETY: `A` and `B` are the same type.
+        unsafe { cast::transmute_checked(x) }
+    }
+
+    /// If `A` equals `B`, then `Vec<A>` equals `Vec<B>`.
+    #[inline]
+    pub fn lift_vec(self) -> Refl<Vec<A>, Vec<B>> {
+        Refl(PhantomData)
+    }
+
+    /// If `A` equals `B`, then `Option<A>` equals `Option<B>`.
+    #[inline]
+    pub fn lift_option(self) -> Refl<Option<A>, Option<B>> {
+        Refl(PhantomData)
+    }
+
+    /// If `A` equals `B`, then `[A]` equals `[B]`.
+    #[inline]
+    pub fn lift_slice(self) -> Refl<[A], [B]> {
+        Refl(PhantomData)
+    }
+
+    /// Convert a `Vec<A>` into a `Vec<B>`.
+    #[inline]
+    pub fn cast_vec(self, x: Vec<A>) -> Vec<B> {
+        self.lift_vec().cast(x)
+    }
+
+    /// Convert an `Option<A>` into an `Option<B>`.
+    #[inline]
+    pub fn cast_option(self, x: Option<A>) -> Option<B> {
+        self.lift_option().cast(x)
+    }
+
+    /// Convert a `&[A]` into a `&[B]`.
+    #[inline]
+    pub fn cast_slice(self, x: &[A]) -> &[B] {
+        self.lift_slice().cast_ref(x)
+    }
+}
+
+impl<A: ?Sized> Default for Refl<A, A> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: ?Sized, B: ?Sized> Clone for Refl<A, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A: ?Sized, B: ?Sized> Copy for Refl<A, B> {}
+
+impl<A: ?Sized, B: ?Sized> Dupe for Refl<A, B> {}
+
+impl<A: ?Sized, B: ?Sized> Debug for Refl<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Refl")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_refl() {
+        let r = Refl::<String, String>::new();
+        assert_eq!(r.cast("test".to_owned()), "test");
+        let r2 = r.symm().trans(r);
+        assert_eq!(r2.cast_option(Some("x".to_owned())), Some("x".to_owned()));
+        assert_eq!(r2.cast_slice(&["y".to_owned()]), ["y"]);
+        assert_eq!(*r2.cast_arc(Arc::new("z".to_owned())), "z");
+        let mut s = "a".to_owned();
+        r2.cast_mut(&mut s).push('b');
+        assert_eq!(r2.cast_ref(&s), "ab");
+        assert_eq!(
+            Refl::<str, str>::new().cast_box("boxed".into()),
+            Box::<str>::from("boxed")
+        );
+    }
+
+    #[test]
+    fn test_refl_test() {
+        fn first_len<T: 'static>(xs: Vec<T>) -> Option<usize> {
+            let refl = Refl::<T, String>::test()?;
+            refl.cast_vec(xs).first().map(|x| x.len())
+        }
+        assert_eq!(first_len(vec!["hello".to_owned()]), Some(5));
+        assert_eq!(first_len(vec![1]), None);
+        assert!(Refl::<str, [u8]>::test().is_none());
+        assert!(Refl::<dyn Debug, dyn Debug>::test().is_some());
+    }
+}
@@ -22,7 +22,7 @@ pub fn derive_default_(input: DeriveInput) -> proc_macro::TokenStream {
     let name = &input.ident;
     let body = default_impl(&input.data);
     let gen = quote! {
-        impl #impl_generics ::std::default::Default for #name #ty_generics #where_clause {
+        impl #impl_generics ::core::default::Default for #name #ty_generics #where_clause {
             fn default() -> Self {
                 #body
             }
@@ -38,7 +38,7 @@ fn default_struct(data: &DataStruct) -> TokenStream {
             let xs = fields.named.iter().map(|f| {
                 let name = &f.ident;
                 quote_spanned! {f.span() =>
-                    #name: ::std::default::Default::default()
+                    #name: ::core::default::Default::default()
                 }
             });
             quote! {
@@ -49,7 +49,7 @@ fn default_struct(data: &DataStruct) -> TokenStream {
             // Self(Default::default(), Default::default())
             let xs = fields.unnamed.iter().map(|f| {
                 quote_spanned! {f.span()=>
-                    ::std::default::Default::default()
+                    ::core::default::Default::default()
                 }
             });
             quote! {
@@ -17,6 +17,9 @@ use syn::parse_macro_input;
 use syn::DeriveInput;
 
 mod default;
+mod ord_by;
+mod pod;
+mod stable_hash;
 mod variant;
 
 /// Derive the [`Default` trait](Default), but without requiring all type arguments to implement [`Default`].
@@ -27,6 +30,53 @@ pub fn derive_default_(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
     default::derive_default_(input)
 }
 
+/// Derive `PartialEq`, `Eq`, `PartialOrd` and `Ord` comparing only the keys listed in
+/// `#[ord_by(...)]`.
+#[proc_macro_derive(OrdBy, attributes(ord_by))]
+pub fn derive_ord_by(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match ord_by::derive_ord_by(input) {
+        Ok(tokens) => tokens,
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Derive `Hash` over only the keys listed in `#[ord_by(...)]`, consistent with `OrdBy`.
+#[proc_macro_derive(HashBy, attributes(ord_by))]
+pub fn derive_hash_by(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match ord_by::derive_hash_by(input) {
+        Ok(tokens) => tokens,
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Derive the `Pod` trait, checking the type is `#[repr(C)]` or `#[repr(transparent)]`,
+/// that every field is `Pod`, and that there is no padding.
+#[proc_macro_derive(Pod)]
+pub fn derive_pod(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match pod::derive_pod(input) {
+        Ok(tokens) => tokens,
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Derive the `StableHash` trait, hashing the fields in declaration order, preceded by
+/// the index of the variant for enums.
+#[proc_macro_derive(StableHash)]
+pub fn derive_stable_hash(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match stable_hash::derive_stable_hash(input) {
+        Ok(tokens) => tokens,
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 /// Derive the `VariantName` trait.
 #[proc_macro_derive(VariantName)]
 pub fn derive_variant_names(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
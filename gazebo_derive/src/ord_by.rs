@@ -0,0 +1,191 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use proc_macro2::TokenStream;
+use quote::format_ident;
+use quote::quote;
+use syn::parse::Parse;
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Expr;
+use syn::Fields;
+use syn::Ident;
+use syn::Token;
+
+/// A single key from `#[ord_by(...)]`.
+enum Key {
+    /// A field name or tuple index, or `key = expr`, evaluated with the fields in scope.
+    Expr(TokenStream),
+    /// `reverse(key)`, comparing in the opposite direction.
+    Reverse(Box<Key>),
+}
+
+impl Parse for Key {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Ident) && input.peek2(syn::token::Paren) {
+            let ident: Ident = input.parse()?;
+            if ident != "reverse" {
+                return Err(syn::Error::new(ident.span(), "Expected `reverse(...)`"));
+            }
+            let content;
+            syn::parenthesized!(content in input);
+            Ok(Key::Reverse(Box::new(content.parse()?)))
+        } else if input.peek(Ident) && input.peek2(Token![=]) {
+            let ident: Ident = input.parse()?;
+            if ident != "key" {
+                return Err(syn::Error::new(ident.span(), "Expected `key = ...`"));
+            }
+            input.parse::<Token![=]>()?;
+            let expr: Expr = input.parse()?;
+            Ok(Key::Expr(quote! { #expr }))
+        } else if input.peek(syn::LitInt) {
+            let index: syn::LitInt = input.parse()?;
+            let ident = format_ident!("_{}", index.base10_parse::<usize>()?);
+            Ok(Key::Expr(quote! { #ident }))
+        } else {
+            let ident: Ident = input.parse()?;
+            Ok(Key::Expr(quote! { #ident }))
+        }
+    }
+}
+
+impl Key {
+    fn expr(&self) -> &TokenStream {
+        match self {
+            Key::Expr(e) => e,
+            Key::Reverse(k) => k.expr(),
+        }
+    }
+
+    fn reversed(&self) -> bool {
+        match self {
+            Key::Expr(_) => false,
+            Key::Reverse(k) => !k.reversed(),
+        }
+    }
+}
+
+/// The keys to compare by, and a pattern binding every field of `Self`.
+struct OrdBy {
+    keys: Vec<Key>,
+    pattern: TokenStream,
+}
+
+impl OrdBy {
+    fn new(input: &DeriveInput) -> syn::Result<Self> {
+        let pattern = match &input.data {
+            Data::Struct(data) => match &data.fields {
+                Fields::Named(fields) => {
+                    let names = fields.named.iter().map(|f| &f.ident);
+                    quote! { Self { #(#names),* } }
+                }
+                Fields::Unnamed(fields) => {
+                    let names = (0..fields.unnamed.len()).map(|i| format_ident!("_{}", i));
+                    quote! { Self ( #(#names),* ) }
+                }
+                Fields::Unit => quote! { Self },
+            },
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "Can only derive OrdBy/HashBy on structs",
+                ));
+            }
+        };
+
+        let mut keys = Vec::new();
+        for attr in &input.attrs {
+            if attr.path().is_ident("ord_by") {
+                keys.extend(attr.parse_args_with(Punctuated::<Key, Token![,]>::parse_terminated)?);
+            }
+        }
+        if keys.is_empty() {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "Expected at least one key in `#[ord_by(...)]`",
+            ));
+        }
+        Ok(OrdBy { keys, pattern })
+    }
+
+    fn eval(&self, key: &Key, this: TokenStream) -> TokenStream {
+        let pattern = &self.pattern;
+        let expr = key.expr();
+        quote! {
+            {
+                #[allow(unused_variables)]
+                let #pattern = #this;
+                #expr
+            }
+        }
+    }
+}
+
+pub(crate) fn derive_ord_by(input: DeriveInput) -> syn::Result<proc_macro::TokenStream> {
+    let ord_by = OrdBy::new(&input)?;
+    let mut eqs = Vec::new();
+    let mut cmps = Vec::new();
+    for key in &ord_by.keys {
+        let x = ord_by.eval(key, quote! { self });
+        let y = ord_by.eval(key, quote! { other });
+        eqs.push(quote! { #x == #y });
+        cmps.push(if key.reversed() {
+            quote! { ::core::cmp::Ord::cmp(&#y, &#x) }
+        } else {
+            quote! { ::core::cmp::Ord::cmp(&#x, &#y) }
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let gen = quote! {
+        impl #impl_generics ::core::cmp::PartialEq for #name #ty_generics #where_clause {
+            fn eq(&self, other: &Self) -> bool {
+                gazebo::eq_chain!(#(#eqs),*)
+            }
+        }
+
+        impl #impl_generics ::core::cmp::Eq for #name #ty_generics #where_clause {}
+
+        impl #impl_generics ::core::cmp::PartialOrd for #name #ty_generics #where_clause {
+            fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                ::core::option::Option::Some(::core::cmp::Ord::cmp(self, other))
+            }
+        }
+
+        impl #impl_generics ::core::cmp::Ord for #name #ty_generics #where_clause {
+            fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
+                gazebo::cmp_chain!(#(#cmps),*)
+            }
+        }
+    };
+    Ok(gen.into())
+}
+
+pub(crate) fn derive_hash_by(input: DeriveInput) -> syn::Result<proc_macro::TokenStream> {
+    let ord_by = OrdBy::new(&input)?;
+    let keys: Vec<_> = ord_by
+        .keys
+        .iter()
+        .map(|key| ord_by.eval(key, quote! { self }))
+        .collect();
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let gen = quote! {
+        impl #impl_generics ::core::hash::Hash for #name #ty_generics #where_clause {
+            fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+                gazebo::hash_chain!(state, #(#keys),*)
+            }
+        }
+    };
+    Ok(gen.into())
+}
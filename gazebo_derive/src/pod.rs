@@ -0,0 +1,96 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use quote::quote;
+use quote::quote_spanned;
+use syn::spanned::Spanned;
+use syn::Data;
+use syn::DeriveInput;
+
+pub(crate) fn derive_pod(mut input: DeriveInput) -> syn::Result<proc_macro::TokenStream> {
+    let data = match &input.data {
+        Data::Struct(data) => data,
+        Data::Enum(x) => {
+            return Err(syn::Error::new_spanned(
+                x.enum_token,
+                "Can't derive Pod for enums",
+            ));
+        }
+        Data::Union(x) => {
+            return Err(syn::Error::new_spanned(
+                x.union_token,
+                "Can't derive Pod for unions",
+            ));
+        }
+    };
+    check_repr(&input)?;
+
+    let tys: Vec<_> = data.fields.iter().map(|f| f.ty.clone()).collect();
+
+    // Every field must be `Pod`, which is an error on the impl for concrete types.
+    let where_clause = input.generics.make_where_clause();
+    for ty in &tys {
+        where_clause
+            .predicates
+            .push(syn::parse2(quote_spanned! {ty.span()=> #ty: gazebo::cast::Pod }).unwrap());
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // Without generics we can check for padding at the definition, otherwise it is checked
+    // when the `gazebo::cast` functions are instantiated.
+    let check = if input.generics.params.is_empty() {
+        quote! {
+            const _: () = <#name as gazebo::cast::Pod>::ASSERT_NO_PADDING;
+        }
+    } else {
+        quote! {}
+    };
+    let gen = quote! {
+        unsafe impl #impl_generics gazebo::cast::Pod for #name #ty_generics #where_clause {
+            const ASSERT_NO_PADDING: () = {
+                // Generic fields are only checked when their own assertion is evaluated.
+                #(let () = <#tys as gazebo::cast::Pod>::ASSERT_NO_PADDING;)*
+                ::core::assert!(
+                    ::core::mem::size_of::<Self>() == 0 #(+ ::core::mem::size_of::<#tys>())*,
+                    "derive(Pod): type has padding"
+                );
+            };
+        }
+        #check
+    };
+    Ok(gen.into())
+}
+
+fn check_repr(input: &DeriveInput) -> syn::Result<()> {
+    let mut ok = false;
+    for attr in &input.attrs {
+        if attr.path().is_ident("repr") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("C") || meta.path.is_ident("transparent") {
+                    ok = true;
+                } else if meta.input.peek(syn::token::Paren) {
+                    // e.g. `align(8)`, `packed(2)`
+                    let content;
+                    syn::parenthesized!(content in meta.input);
+                    content.parse::<proc_macro2::TokenStream>()?;
+                }
+                Ok(())
+            })?;
+        }
+    }
+    if ok {
+        Ok(())
+    } else {
+        Err(syn::Error::new(
+            input.ident.span(),
+            "Can only derive Pod for `#[repr(C)]` or `#[repr(transparent)]` structs",
+        ))
+    }
+}
@@ -0,0 +1,89 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use proc_macro2::TokenStream;
+use quote::format_ident;
+use quote::quote;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Fields;
+use syn::GenericParam;
+
+pub(crate) fn derive_stable_hash(mut input: DeriveInput) -> syn::Result<proc_macro::TokenStream> {
+    for param in &mut input.generics.params {
+        if let GenericParam::Type(param) = param {
+            param
+                .bounds
+                .push(syn::parse_quote!(gazebo::stable_hash::StableHash));
+        }
+    }
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, hashes) = fields(&data.fields);
+            quote! {
+                let Self #pattern = self;
+                #hashes
+            }
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().enumerate().map(|(i, v)| {
+                let name = &v.ident;
+                let index = i as u32;
+                let (pattern, hashes) = fields(&v.fields);
+                quote! {
+                    Self::#name #pattern => {
+                        gazebo::stable_hash::StableHash::stable_hash(&#index, hasher);
+                        #hashes
+                    }
+                }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(x) => {
+            return Err(syn::Error::new_spanned(
+                x.union_token,
+                "Can't derive StableHash for unions",
+            ));
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let gen = quote! {
+        impl #impl_generics gazebo::stable_hash::StableHash for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn stable_hash(&self, hasher: &mut gazebo::stable_hash::StableHasher) {
+                #body
+            }
+        }
+    };
+    Ok(gen.into())
+}
+
+/// A pattern binding the fields to `f0`, `f1`..., and the statements hashing them in order.
+fn fields(fields: &Fields) -> (TokenStream, TokenStream) {
+    let bindings: Vec<_> = (0..fields.len()).map(|i| format_ident!("f{}", i)).collect();
+    let pattern = match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|f| &f.ident);
+            quote! { { #(#names: #bindings),* } }
+        }
+        Fields::Unnamed(_) => quote! { ( #(#bindings),* ) },
+        Fields::Unit => quote! {},
+    };
+    let hashes = quote! {
+        #(gazebo::stable_hash::StableHash::stable_hash(#bindings, hasher);)*
+    };
+    (pattern, hashes)
+}